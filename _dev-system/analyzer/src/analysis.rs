@@ -0,0 +1,90 @@
+//! Per-file metrics and drag, shared by the scan and by tools that check a
+//! single file on demand.
+use crate::drivers::config::analyze_config;
+use crate::drivers::css::analyze_css;
+use crate::drivers::html::analyze_html;
+use crate::drivers::rescript::analyze_rescript;
+use crate::drivers::rust::analyze_rust;
+use crate::drivers::CommonMetrics;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The drag part of `settings` in `config/efficiency.json`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DragSettings {
+    pub nesting_weight: f64,
+    pub density_weight: f64,
+    pub state_weight: f64,
+    pub max_depth_threshold: usize,
+    pub drag_target: f64,
+}
+
+/// Driver (and profile) name for a file: `rust`, `rescript`, `web`, `css` or `config`.
+pub fn driver_for(path: &Path) -> &'static str {
+    match path.extension().and_then(|s| s.to_str()).unwrap_or("") {
+        "rs" => "rust",
+        "res" => "rescript",
+        "jsx" | "js" | "html" => "web",
+        "css" => "css",
+        _ => "config",
+    }
+}
+
+pub fn analyze(driver: &str, content: &str, dict: &HashMap<String, f64>) -> CommonMetrics {
+    match driver {
+        "rust" => analyze_rust(content, dict).unwrap_or_default(),
+        "rescript" => analyze_rescript(content, dict).unwrap_or_default(),
+        "web" => analyze_html(content, dict).unwrap_or_default(),
+        "css" => analyze_css(content, dict).unwrap_or_default(),
+        _ => analyze_config(content, dict).unwrap_or_default(),
+    }
+}
+
+/// Drag before the failure multiplier from `AnalyzerState`. `path` is relative
+/// to the project root; deep paths add a penalty.
+pub fn drag(metrics: &CommonMetrics, settings: &DragSettings, path: &str) -> f64 {
+    let per_line = |count: f64| if metrics.loc > 0 { count / metrics.loc as f64 } else { 0.0 };
+    let density = per_line(metrics.logic_count as f64);
+    let complexity_density = per_line(metrics.complexity_penalty);
+    let state_density = per_line(metrics.state_count as f64);
+    let dir_depth = Path::new(path).components().count().saturating_sub(settings.max_depth_threshold) as f64;
+    let depth_penalty = dir_depth * 0.5;
+    1.0 + (metrics.max_nesting as f64 * settings.nesting_weight)
+        + (density * settings.density_weight)
+        + (complexity_density * 20.0)
+        + (state_density * settings.state_weight)
+        + depth_penalty
+}
+
+/// What a single-file analysis needs from `config/efficiency.json`.
+pub struct AnalysisConfig {
+    pub drag: DragSettings,
+    /// `profiles.<driver>.complexity_dictionary`
+    pub dictionaries: HashMap<String, HashMap<String, f64>>,
+}
+
+#[derive(Deserialize)]
+struct ConfigFile {
+    settings: DragSettings,
+    #[serde(default)]
+    profiles: HashMap<String, ProfileFile>,
+}
+
+#[derive(Deserialize)]
+struct ProfileFile {
+    #[serde(default)]
+    complexity_dictionary: HashMap<String, f64>,
+}
+
+impl AnalysisConfig {
+    pub fn load_from(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file: ConfigFile = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let dictionaries = file.profiles.into_iter().map(|(name, p)| (name, p.complexity_dictionary)).collect();
+        Ok(AnalysisConfig { drag: file.settings, dictionaries })
+    }
+
+    pub fn dictionary(&self, driver: &str) -> HashMap<String, f64> {
+        self.dictionaries.get(driver).cloned().unwrap_or_default()
+    }
+}
@@ -19,6 +19,31 @@ pub struct CommonMetrics {
     pub dependencies: Vec<String>, // Explicit imports/modules
 }
 
+/// A named definition and the lines it spans (1-based, inclusive).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: &'static str,
+    pub signature: String,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// The declaration text of a symbol starting at `line`: up to the opening
+/// brace or terminating semicolon, on one line.
+pub fn signature_at(content: &str, line: usize) -> String {
+    let mut signature = String::new();
+    for text in content.lines().skip(line.saturating_sub(1)).take(6) {
+        let end = text.find('{').or_else(|| text.trim_end().ends_with(';').then(|| text.trim_end().len() - 1));
+        signature.push(' ');
+        signature.push_str(&text[..end.unwrap_or(text.len())]);
+        if end.is_some() {
+            break;
+        }
+    }
+    signature.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
 pub enum EfficiencyOverride {
     None,
     Ignore,
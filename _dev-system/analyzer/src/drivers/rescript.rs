@@ -1,10 +1,11 @@
-use super::{CommonMetrics, strip_code_modular};
+use super::{signature_at, strip_code_modular, CommonMetrics, Symbol};
 use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 struct Scope {
     name: String,
     start_line: usize,
+    end_line: usize,
     depth: usize,
     complexity: f64,
     // density: f64, // Calculated at end
@@ -23,6 +24,12 @@ pub struct RescriptParser<'a> {
     in_string: bool,
     string_char: char,
     pending_function_name: Option<String>,
+    /// Set by `symbols`, which needs exact lines and names. The scan keeps its
+    /// original reading (it also skips the character after each brace, and a
+    /// comma in a parameter list drops the function name), so its scores stay
+    /// comparable with earlier runs.
+    exact: bool,
+    paren_depth: usize,
 }
 
 impl<'a> RescriptParser<'a> {
@@ -48,6 +55,7 @@ impl<'a> RescriptParser<'a> {
             scope_stack: vec![Scope {
                 name: "root".to_string(),
                 start_line: 1,
+                end_line: 1,
                 depth: 0,
                 complexity: 0.0,
                 is_function: false,
@@ -56,6 +64,8 @@ impl<'a> RescriptParser<'a> {
             in_string: false,
             string_char: '"',
             pending_function_name: None,
+            exact: false,
+            paren_depth: 0,
         }
     }
 
@@ -70,6 +80,58 @@ impl<'a> RescriptParser<'a> {
         self.extract_dependencies(&stripped);
 
         // Second pass: Semantic traversal
+        self.walk();
+
+        self.metrics.max_nesting = self.completed_scopes.iter().map(|s| s.depth).max().unwrap_or(0);
+
+        // Find Hotspot (Function with highest complexity)
+        let mut max_score = 0.0;
+
+        // Add root complexity to the list for comparison? No, we want specific functions.
+
+        for scope in &self.completed_scopes {
+            if scope.is_function {
+                // Heuristic: Scope complexity is high
+                if scope.complexity > max_score {
+                    max_score = scope.complexity;
+                    self.metrics.hotspot_symbol = Some(format!("Function: `{}`", scope.name));
+                    self.metrics.hotspot_lines = Some((scope.start_line, self.metrics.loc.min(scope.start_line + 20)));
+                    self.metrics.hotspot_reason = Some(format!("High Local Complexity ({:.1}). Logic heavy.", scope.complexity));
+                }
+            }
+        }
+
+        // Fallback: If no functions detected (maybe it's a script), check if root is complex
+        if self.metrics.hotspot_symbol.is_none() && self.metrics.complexity_penalty > 50.0 {
+             self.metrics.hotspot_reason = Some("Global Scope Complexity".to_string());
+        }
+
+        Ok(self.metrics)
+    }
+
+    /// Function scopes (`let name = ... => {`) with the lines they span.
+    pub fn symbols(mut self) -> Vec<Symbol> {
+        self.exact = true;
+        self.walk();
+        let content = self.content;
+        let mut symbols: Vec<Symbol> = self
+            .completed_scopes
+            .into_iter()
+            .filter(|scope| scope.is_function)
+            .map(|scope| Symbol {
+                signature: signature_at(content, scope.start_line),
+                name: scope.name,
+                kind: "function",
+                start_line: scope.start_line,
+                end_line: scope.end_line,
+            })
+            .collect();
+        symbols.sort_by_key(|s| s.start_line);
+        symbols
+    }
+
+    /// One pass over the source: logic metrics plus every scope in `completed_scopes`.
+    fn walk(&mut self) {
         while self.pos < self.chars.len() {
             let c = self.chars[self.pos];
             
@@ -129,6 +191,8 @@ impl<'a> RescriptParser<'a> {
                         // }
                     }
                 },
+                '(' => self.paren_depth += 1,
+                ')' => self.paren_depth = self.paren_depth.saturating_sub(1),
                 '-' => {
                     if self.peek(1) == '>' {
                         // Pipe `->`
@@ -170,7 +234,8 @@ impl<'a> RescriptParser<'a> {
                 }
             } else {
                 // Allow dot, brackets, angle brackets (generics/JSX) to avoid resetting pending name
-                if !c.is_whitespace() && c != '=' && c != '(' && c != ')' && c != ':' && c != '.' && c != '[' && c != ']' && c != '<' && c != '>' {
+                // Commas and labels inside a parameter list (`(~a, b) =>`) keep it too
+                if (!self.exact || self.paren_depth == 0) && !c.is_whitespace() && c != '=' && c != '(' && c != ')' && c != ':' && c != '.' && c != '[' && c != ']' && c != '<' && c != '>' {
                     // Reset pending name on unexpected chars (like `;` or `,`)
                     self.pending_function_name = None;
                 }
@@ -178,37 +243,11 @@ impl<'a> RescriptParser<'a> {
             }
         }
 
-        // Finalize
         // Pop any remaining scopes (should just be root)
-        while let Some(scope) = self.scope_stack.pop() {
+        while let Some(mut scope) = self.scope_stack.pop() {
+            scope.end_line = self.line;
             self.completed_scopes.push(scope);
         }
-
-        self.metrics.max_nesting = self.completed_scopes.iter().map(|s| s.depth).max().unwrap_or(0);
-
-        // Find Hotspot (Function with highest complexity)
-        let mut max_score = 0.0;
-
-        // Add root complexity to the list for comparison? No, we want specific functions.
-
-        for scope in &self.completed_scopes {
-            if scope.is_function {
-                // Heuristic: Scope complexity is high
-                if scope.complexity > max_score {
-                    max_score = scope.complexity;
-                    self.metrics.hotspot_symbol = Some(format!("Function: `{}`", scope.name));
-                    self.metrics.hotspot_lines = Some((scope.start_line, self.metrics.loc.min(scope.start_line + 20)));
-                    self.metrics.hotspot_reason = Some(format!("High Local Complexity ({:.1}). Logic heavy.", scope.complexity));
-                }
-            }
-        }
-
-        // Fallback: If no functions detected (maybe it's a script), check if root is complex
-        if self.metrics.hotspot_symbol.is_none() && self.metrics.complexity_penalty > 50.0 {
-             self.metrics.hotspot_reason = Some("Global Scope Complexity".to_string());
-        }
-
-        Ok(self.metrics)
     }
 
     fn push_scope(&mut self) {
@@ -226,16 +265,20 @@ impl<'a> RescriptParser<'a> {
         self.scope_stack.push(Scope {
             name,
             start_line: self.line,
+            end_line: self.line,
             depth: current_depth,
             complexity: 0.0,
             is_function: is_func,
         });
-        self.pos += 1;
+        if !self.exact {
+            self.pos += 1;
+        }
     }
 
     fn pop_scope(&mut self) {
         if self.scope_stack.len() > 1 {
-            if let Some(scope) = self.scope_stack.pop() {
+            if let Some(mut scope) = self.scope_stack.pop() {
+                scope.end_line = self.line;
                 // Propagate complexity to parent (bubbling up)
                 if let Some(parent) = self.scope_stack.last_mut() {
                     parent.complexity += scope.complexity * 0.5; // Child complexity contributes partial weight to parent
@@ -243,7 +286,9 @@ impl<'a> RescriptParser<'a> {
                 self.completed_scopes.push(scope);
             }
         }
-        self.pos += 1;
+        if !self.exact {
+            self.pos += 1;
+        }
     }
     
     fn current_scope_mut(&mut self) -> &mut Scope {
@@ -374,3 +419,7 @@ pub fn analyze_rescript(content: &str, dict: &HashMap<String, f64>) -> anyhow::R
     let parser = RescriptParser::new(content);
     parser.analyze(dict)
 }
+
+pub fn rescript_symbols(content: &str) -> Vec<Symbol> {
+    RescriptParser::new(content).symbols()
+}
@@ -1,9 +1,12 @@
-use super::CommonMetrics;
+use super::{signature_at, CommonMetrics, Symbol};
 use quote::ToTokens;
 use std::cmp;
 use syn::{
+    spanned::Spanned,
     visit::{self, Visit},
-    Block, Expr, ExprAssign, ExprIf, ExprLoop, ExprMatch, ItemFn, ItemMod, ItemUse, Pat, PatType,
+    Block, Expr, ExprAssign, ExprIf, ExprLoop, ExprMatch, Ident, ImplItemFn, ItemConst, ItemEnum, ItemFn,
+    ItemImpl, ItemMacro, ItemMod, ItemStatic, ItemStruct, ItemTrait, ItemType, ItemUse, Pat, PatType,
+    TraitItemFn,
 };
 
 #[derive(Default)]
@@ -99,3 +102,96 @@ pub fn analyze_rust(
 
     Ok(walker.metrics)
 }
+
+/// Collects items, with methods named `Type::method` after their impl or trait.
+struct SymbolWalker<'a> {
+    content: &'a str,
+    symbols: Vec<Symbol>,
+    container: Option<String>,
+}
+
+impl SymbolWalker<'_> {
+    fn push(&mut self, kind: &'static str, ident: &Ident, item: &impl Spanned) {
+        let name = match (&self.container, kind) {
+            (Some(container), "method") => format!("{}::{}", container, ident),
+            _ => ident.to_string(),
+        };
+        let start_line = ident.span().start().line;
+        self.symbols.push(Symbol {
+            name,
+            kind,
+            signature: signature_at(self.content, start_line),
+            start_line,
+            end_line: item.span().end().line,
+        });
+    }
+}
+
+impl<'ast> Visit<'ast> for SymbolWalker<'_> {
+    fn visit_item_fn(&mut self, i: &'ast ItemFn) {
+        self.push("fn", &i.sig.ident, i);
+        visit::visit_item_fn(self, i);
+    }
+
+    fn visit_item_struct(&mut self, i: &'ast ItemStruct) {
+        self.push("struct", &i.ident, i);
+    }
+
+    fn visit_item_enum(&mut self, i: &'ast ItemEnum) {
+        self.push("enum", &i.ident, i);
+    }
+
+    fn visit_item_type(&mut self, i: &'ast ItemType) {
+        self.push("type", &i.ident, i);
+    }
+
+    fn visit_item_const(&mut self, i: &'ast ItemConst) {
+        self.push("const", &i.ident, i);
+    }
+
+    fn visit_item_static(&mut self, i: &'ast ItemStatic) {
+        self.push("static", &i.ident, i);
+    }
+
+    fn visit_item_macro(&mut self, i: &'ast ItemMacro) {
+        if let Some(ident) = &i.ident {
+            self.push("macro", ident, i);
+        }
+    }
+
+    fn visit_item_mod(&mut self, i: &'ast ItemMod) {
+        self.push("mod", &i.ident, i);
+        visit::visit_item_mod(self, i);
+    }
+
+    fn visit_item_trait(&mut self, i: &'ast ItemTrait) {
+        self.push("trait", &i.ident, i);
+        let outer = self.container.replace(i.ident.to_string());
+        visit::visit_item_trait(self, i);
+        self.container = outer;
+    }
+
+    fn visit_item_impl(&mut self, i: &'ast ItemImpl) {
+        // `impl Display for Foo` contributes `Foo::fmt`
+        let self_ty = i.self_ty.to_token_stream().to_string().replace(' ', "");
+        let outer = self.container.replace(self_ty);
+        visit::visit_item_impl(self, i);
+        self.container = outer;
+    }
+
+    fn visit_impl_item_fn(&mut self, i: &'ast ImplItemFn) {
+        self.push("method", &i.sig.ident, i);
+        visit::visit_impl_item_fn(self, i);
+    }
+
+    fn visit_trait_item_fn(&mut self, i: &'ast TraitItemFn) {
+        self.push("method", &i.sig.ident, i);
+    }
+}
+
+pub fn rust_symbols(content: &str) -> anyhow::Result<Vec<Symbol>> {
+    let syntax = syn::parse_file(content)?;
+    let mut walker = SymbolWalker { content, symbols: Vec::new(), container: None };
+    walker.visit_file(&syntax);
+    Ok(walker.symbols)
+}
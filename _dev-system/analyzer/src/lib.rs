@@ -1,4 +1,7 @@
+pub mod analysis;
 pub mod drivers;
 pub mod graph;
 pub mod rescript_auto_discovery;
 pub mod resolver; // Expose graph if needed for tests, but mainly resolver
+pub mod state;
+pub mod symbols;
@@ -1,10 +1,9 @@
-mod drivers;
 mod consolidator;
 mod guard;
-mod graph;
-mod state;
 mod feedback;
 
+use efficiency_analyzer::analysis::{self, DragSettings};
+use efficiency_analyzer::{drivers, graph, state};
 use efficiency_analyzer::resolver::Resolver;
 use std::fs::{self, OpenOptions};
 use std::io::Write;
@@ -17,11 +16,6 @@ use graph::DependencyGraph;
 
 use drivers::{parse_header, EfficiencyOverride};
 use consolidator::{FolderStats, calculate_merge_score, find_recursive_clusters, FileInfo};
-use drivers::rust::analyze_rust;
-use drivers::rescript::analyze_rescript;
-use drivers::html::analyze_html;
-use drivers::css::analyze_css;
-use drivers::config::analyze_config;
 
 #[derive(Debug, Deserialize)]
 struct EfficiencyConfig {
@@ -63,11 +57,8 @@ struct Settings {
     #[allow(dead_code)]
     max_session_complexity: f64,
     merge_score_threshold: f64,
-    nesting_weight: f64,
-    density_weight: f64,
-    drag_target: f64,
-    state_weight: f64,
-    max_depth_threshold: usize,
+    #[serde(flatten)]
+    drag: DragSettings,
 }
 
 #[derive(Debug, Deserialize)]
@@ -291,9 +282,9 @@ fn sync_all_architectural_tasks(buffer: &HashMap<String, Vec<WorkUnit>>, config:
     };
 
     let surgical_obj = config.templates.surgical_objective
-        .replace("{nesting_w}", &format!("{:.2}", config.settings.nesting_weight))
-        .replace("{density_w}", &format!("{:.2}", config.settings.density_weight))
-        .replace("{drag_t}", &format!("{:.2}", config.settings.drag_target));
+        .replace("{nesting_w}", &format!("{:.2}", config.settings.drag.nesting_weight))
+        .replace("{density_w}", &format!("{:.2}", config.settings.drag.density_weight))
+        .replace("{drag_t}", &format!("{:.2}", config.settings.drag.drag_target));
 
     let merge_obj = config.templates.merge_objective
         .replace("{merge_t}", &format!("{:.2}", config.settings.merge_score_threshold));
@@ -506,18 +497,12 @@ fn main() -> Result<()> {
                 if taxonomy == "ignored" { continue; }
 
                 let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
-                let d_name = match ext { "rs" => "rust", "res" => "rescript", "jsx"|"js"|"html" => "web", "css" => "css", _ => "config" };
+                let d_name = analysis::driver_for(path);
                 let platform = if ext == "rs" || path.to_string_lossy().contains("backend") { "backend" } else { "frontend" };
 
                 let dict = config.profiles.get(d_name).map(|p| &p.complexity_dictionary).unwrap_or(&default_dict);
 
-                let metrics = match d_name {
-                    "rust" => analyze_rust(&content, dict).unwrap_or_default(),
-                    "rescript" => analyze_rescript(&content, dict).unwrap_or_default(),
-                    "web" => analyze_html(&content, dict).unwrap_or_default(),
-                    "css" => analyze_css(&content, dict).unwrap_or_default(),
-                    _ => analyze_config(&content, dict).unwrap_or_default(),
-                };
+                let metrics = analysis::analyze(d_name, &content, dict);
 
                 if metrics.loc > 0 {
                     total_loc += metrics.loc;
@@ -610,16 +595,10 @@ fn main() -> Result<()> {
         if let Some(exceptions) = &config.exceptions { for rule in exceptions { if p_str.contains(&rule.pattern) { if let Some(m) = rule.multiplier { p_mod *= m; } break; } } }
 
         let cohesion_bonus = 1.0 + (0.5 - dependency_density).max(0.0);
-        let complexity_density = if metrics.loc > 0 { metrics.complexity_penalty / metrics.loc as f64 } else { 0.0 };
-        let state_density = if metrics.loc > 0 { metrics.state_count as f64 / metrics.loc as f64 } else { 0.0 };
 
         let clean_path_from_root = p_str.replace("../../", "");
-        let clean_path_obj = Path::new(&clean_path_from_root);
-        let dir_depth = clean_path_obj.components().count().saturating_sub(config.settings.max_depth_threshold) as f64;
-        let depth_penalty = if dir_depth > 0.0 { dir_depth * 0.5 } else { 0.0 };
-
         let failure_penalty_mult = state.get_drag_multiplier(p_str);
-        let drag = (1.0 + (metrics.max_nesting as f64 * config.settings.nesting_weight) + (density * config.settings.density_weight) + (complexity_density * 20.0) + (state_density * config.settings.state_weight) + depth_penalty) * failure_penalty_mult;
+        let drag = analysis::drag(metrics, &config.settings.drag, &clean_path_from_root) * failure_penalty_mult;
 
         let limit = calculate_dynamic_limit(drag, p_mod, cohesion_bonus, dynamic_base, &config, p_str);
 
@@ -640,8 +619,8 @@ fn main() -> Result<()> {
             // 4. Surgical Refactor Task (De-bloat) - Only for known modules
             if metrics.loc > limit {
                 is_surgical = true;
-                let nesting_factor = metrics.max_nesting as f64 * config.settings.nesting_weight;
-                let density_factor = density * config.settings.density_weight;
+                let nesting_factor = metrics.max_nesting as f64 * config.settings.drag.nesting_weight;
+                let density_factor = density * config.settings.drag.density_weight;
                 let breakdown = format!("[Nesting: {:.2}, Density: {:.2}, Coupling: {:.2}] | Drag: {:.2} | LOC: {}/{}",
                     nesting_factor, density_factor, coupling_score, drag, metrics.loc, limit);
                 let mut reason = breakdown;
@@ -708,7 +687,7 @@ fn main() -> Result<()> {
         // --- Circularity Prevention: Skip unreachable files OR surgical files for merging ---
         if dead_files.contains(p_str) || buffer.values().any(|units| units.iter().any(|u| if let WorkUnit::Surgical { file, .. } = u { file == p_str } else { false })) { continue; }
 
-        let drag = 1.0 + (metrics.max_nesting as f64 * config.settings.nesting_weight) + ((metrics.logic_count as f64 / metrics.loc as f64) * config.settings.density_weight) + ((metrics.complexity_penalty / metrics.loc as f64) * 20.0);
+        let drag = 1.0 + (metrics.max_nesting as f64 * config.settings.drag.nesting_weight) + ((metrics.logic_count as f64 / metrics.loc as f64) * config.settings.drag.density_weight) + ((metrics.complexity_penalty / metrics.loc as f64) * 20.0);
 
         // We use extension as a proxy for language/compatibility
         let ext = Path::new(p_str).extension().and_then(|s| s.to_str()).unwrap_or("").to_string();
@@ -785,7 +764,7 @@ fn main() -> Result<()> {
         // Structural: Deep nesting check
         let clean_dir_str = dir.replace("../../", "");
         let clean_dir = Path::new(&clean_dir_str);
-        let dir_depth = clean_dir.components().count().saturating_sub(config.settings.max_depth_threshold);
+        let dir_depth = clean_dir.components().count().saturating_sub(config.settings.drag.max_depth_threshold);
         if dir_depth > 0 {
              buffer.entry("system".to_string()).or_default().push(WorkUnit::Structural {
                 file: dir.clone(),
@@ -23,7 +23,13 @@ pub struct AnalyzerState {
 
 impl AnalyzerState {
     pub fn load() -> Self {
-        let path = Path::new("../analyzer_state.json");
+        Self::load_from("../analyzer_state.json")
+    }
+
+    /// Loads the state from an explicit path (e.g. `_dev-system/analyzer_state.json`
+    /// when running from the project root instead of the analyzer folder).
+    pub fn load_from(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
         if path.exists() {
             if let Ok(content) = fs::read_to_string(path) {
                 if let Ok(state) = serde_json::from_str(&content) {
@@ -35,6 +41,10 @@ impl AnalyzerState {
     }
 
     pub fn save(&self) -> anyhow::Result<()> {
+        self.save_to("../analyzer_state.json")
+    }
+
+    pub fn save_to(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
         if !self.dirty {
             return Ok(());
         }
@@ -43,7 +53,7 @@ impl AnalyzerState {
             .create(true)
             .write(true)
             .truncate(true)
-            .open("../analyzer_state.json")?;
+            .open(path)?;
         file.write_all(json.as_bytes())?;
         Ok(())
     }
@@ -56,7 +66,6 @@ impl AnalyzerState {
         self.dirty = true;
     }
 
-    #[allow(dead_code)]
     pub fn record_action(&mut self, file_path: &str, action: &str) {
         let entry = self.files.entry(file_path.to_string()).or_default();
         entry.last_action = Some(action.to_string());
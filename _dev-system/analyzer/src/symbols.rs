@@ -0,0 +1,92 @@
+//! Symbol lookup across a source tree, built on the language drivers.
+use crate::drivers::rescript::rescript_symbols;
+use crate::drivers::rust::rust_symbols;
+use crate::drivers::Symbol;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+
+/// Build output and vendored code; never worth searching.
+const SKIPPED_DIRS: [&str; 3] = ["target", "node_modules", "_build"];
+/// ReScript build output. Other `lib` folders are often source.
+const SKIPPED_PATHS: [&str; 2] = ["lib/bs", "lib/ocaml"];
+
+/// Symbols defined in one file, in source order. Unsupported languages yield none.
+pub fn file_symbols(path: &Path) -> anyhow::Result<Vec<Symbol>> {
+    let content = fs::read_to_string(path)?;
+    let symbols = match path.extension().and_then(|e| e.to_str()) {
+        Some("rs") => rust_symbols(&content)?,
+        Some("res") | Some("resi") => rescript_symbols(&content),
+        _ => Vec::new(),
+    };
+    Ok(symbols)
+}
+
+/// Every definition of `name` under `root`. `Type::method` matches exactly,
+/// a bare name also matches methods of that name.
+pub fn find_definitions(root: &Path, name: &str) -> Vec<(PathBuf, Symbol)> {
+    let mut found = Vec::new();
+    for path in source_files(root) {
+        // Files that fail to parse are skipped; the search is best effort
+        let Ok(symbols) = file_symbols(&path) else { continue };
+        for symbol in symbols {
+            if symbol.name == name || symbol.name.rsplit("::").next() == Some(name) {
+                found.push((path.clone(), symbol));
+            }
+        }
+    }
+    found
+}
+
+pub struct Reference {
+    pub path: PathBuf,
+    pub line: usize,
+    pub text: String,
+}
+
+/// Lines under `root` mentioning `name` as a whole word.
+pub fn find_references(root: &Path, name: &str) -> anyhow::Result<Vec<Reference>> {
+    let name = name.rsplit("::").next().unwrap_or(name);
+    // An empty word would match every line of every file
+    if name.is_empty() {
+        anyhow::bail!("no name to search for");
+    }
+    let mut found = Vec::new();
+    for path in source_files(root) {
+        let Ok(content) = fs::read_to_string(&path) else { continue };
+        for (i, text) in content.lines().enumerate() {
+            if contains_word(text, name) {
+                found.push(Reference { path: path.clone(), line: i + 1, text: text.trim().to_string() });
+            }
+        }
+    }
+    Ok(found)
+}
+
+fn contains_word(text: &str, word: &str) -> bool {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(word).any(|(i, _)| {
+        let before = text[..i].chars().next_back();
+        let after = text[i + word.len()..].chars().next();
+        !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+    })
+}
+
+fn source_files(root: &Path) -> Vec<PathBuf> {
+    let searched = |entry: &DirEntry| {
+        let name = entry.file_name().to_string_lossy();
+        let skipped_dir = entry.file_type().is_dir()
+            && (SKIPPED_DIRS.contains(&name.as_ref()) || SKIPPED_PATHS.iter().any(|path| entry.path().ends_with(path)));
+        entry.depth() == 0 || !(name.starts_with('.') || skipped_dir)
+    };
+    let mut files: Vec<PathBuf> = WalkDir::new(root)
+        .into_iter()
+        .filter_entry(searched)
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+        .map(DirEntry::into_path)
+        .filter(|p| matches!(p.extension().and_then(|e| e.to_str()), Some("rs" | "res" | "resi")))
+        .collect();
+    files.sort();
+    files
+}
@@ -0,0 +1,18 @@
+use efficiency_analyzer::analysis::{drag, DragSettings};
+use efficiency_analyzer::drivers::rust::analyze_rust;
+use std::collections::HashMap;
+
+#[test]
+fn test_drag_adds_depth_penalty_for_deep_paths() {
+    let settings = DragSettings {
+        nesting_weight: 0.5,
+        density_weight: 1.2,
+        state_weight: 6.0,
+        max_depth_threshold: 2,
+        drag_target: 1.8,
+    };
+    let metrics = analyze_rust("fn a() {}\n", &HashMap::new()).unwrap();
+    let shallow = drag(&metrics, &settings, "src/a.rs");
+    let deep = drag(&metrics, &settings, "src/x/y/a.rs");
+    assert!((deep - shallow - 1.0).abs() < 1e-9, "two extra levels should add 1.0, got {} vs {}", deep, shallow);
+}
@@ -0,0 +1,92 @@
+use efficiency_analyzer::drivers::rescript::rescript_symbols;
+use efficiency_analyzer::drivers::rust::rust_symbols;
+use efficiency_analyzer::symbols::{find_definitions, find_references};
+use std::fs;
+use std::path::PathBuf;
+
+#[test]
+fn test_rust_symbols_with_methods_and_line_ranges() {
+    let content = r#"
+/// Docs
+pub struct Walker {
+    depth: usize,
+}
+
+impl Walker {
+    pub fn new(
+        depth: usize,
+    ) -> Self {
+        Walker { depth }
+    }
+}
+
+fn main() {}
+"#;
+    let symbols = rust_symbols(content).unwrap();
+    let names: Vec<(&str, &str)> = symbols.iter().map(|s| (s.kind, s.name.as_str())).collect();
+    assert_eq!(names, vec![("struct", "Walker"), ("method", "Walker::new"), ("fn", "main")]);
+
+    let new = &symbols[1];
+    assert_eq!((new.start_line, new.end_line), (8, 12));
+    assert_eq!(new.signature, "pub fn new( depth: usize, ) -> Self");
+    assert_eq!(symbols[0].signature, "pub struct Walker");
+}
+
+#[test]
+fn test_rescript_function_symbols() {
+    let content = r#"
+let add = (a, b) => {
+  a + b
+}
+
+let make = (~label) => {
+  let inner = () => {
+    label
+  }
+  inner()
+}
+"#;
+    let symbols = rescript_symbols(content);
+    let ranges: Vec<(&str, usize, usize)> = symbols.iter().map(|s| (s.name.as_str(), s.start_line, s.end_line)).collect();
+    assert_eq!(ranges, vec![("add", 2, 4), ("make", 6, 11), ("inner", 7, 9)]);
+    assert_eq!(symbols[0].signature, "let add = (a, b) =>");
+}
+
+/// A fresh project directory under the system temp dir.
+fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("analyzer-symbols-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&root);
+    for (path, content) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    root
+}
+
+#[test]
+fn test_empty_names_are_rejected() {
+    let root = project("empty", &[("src/main.rs", "fn main() {}\n")]);
+    assert!(find_references(&root, "").is_err());
+    assert!(find_references(&root, "Walker::").is_err());
+    assert_eq!(find_references(&root, "main").unwrap().len(), 1);
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_nested_lib_dirs_are_searched() {
+    let root = project(
+        "lib",
+        &[
+            ("src/lib/parse.rs", "pub fn parse_line() {}\n"),
+            ("src/Main.res", "let run = () => {\n  Parse.parse_line()\n}\n"),
+            ("lib/bs/src/Main.res", "let run = () => {\n  parse_line()\n}\n"),
+            ("target/debug/build/out.rs", "pub fn parse_line() {}\n"),
+        ],
+    );
+    let definitions: Vec<PathBuf> = find_definitions(&root, "parse_line").into_iter().map(|(path, _)| path).collect();
+    assert_eq!(definitions, vec![root.join("src/lib/parse.rs")]);
+    let references: Vec<PathBuf> = find_references(&root, "parse_line").unwrap().into_iter().map(|r| r.path).collect();
+    assert_eq!(references, vec![root.join("src/Main.res"), root.join("src/lib/parse.rs")]);
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,641 @@
+use crate::compaction::{self, KEEP_MESSAGES};
+use crate::config::Config;
+use crate::events::{AgentEvent, ConsoleReporter, Reporter};
+use crate::interrupt::Interrupt;
+use crate::redact::{self, Redactor};
+use crate::scheduler::FileLocks;
+use crate::session::Session;
+use crate::tools::{execute_tool, ToolCall, ToolResult};
+use crate::llm::{Image, Message};
+use crate::mcp::McpTool;
+use crate::models::{Models, Role};
+use crate::prompts::Prompts;
+use crate::usage::{self, ProfileUsage, UsageRecord, UsageTotals};
+use crate::{git, verify};
+use similar::TextDiff;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// How a Think -> Act -> Observe loop came to an end.
+enum LoopOutcome {
+    /// The model called `finish` and the work passed verification.
+    Finished,
+    /// The model changed the plan (`update_plan` / `complete_step`).
+    PlanChanged,
+    /// The loop budget ran out.
+    Exhausted,
+    /// The request to the model failed.
+    Failed,
+    /// The user pressed Ctrl-C and chose to stop.
+    Interrupted,
+}
+
+pub struct Agent {
+    /// The model profiles, picked per request by role.
+    models: Models,
+    /// The prompt templates, with the system prompt's fixed slots filled.
+    prompts: Prompts,
+    pub session: Session,
+    config: Arc<Config>,
+    /// Shared locks and this agent's owner id, when running as a parallel sub-agent.
+    locks: Option<(FileLocks, usize)>,
+    reporter: Arc<dyn Reporter>,
+    /// Where tools run: `.`, or the session's worktree when isolated.
+    workdir: PathBuf,
+    /// Images for the next request (`--image`, `view_image`); sent once.
+    images: Vec<Image>,
+    /// Tools of the connected MCP servers.
+    mcp_tools: Vec<McpTool>,
+    interrupt: Interrupt,
+    /// Masks secrets in what the model, the terminal and the session file see.
+    redactor: Redactor,
+}
+
+impl Agent {
+    pub fn new(models: Models, prompts: Prompts, session: Session, config: Arc<Config>) -> Self {
+        let redactor = Redactor::new(&config);
+        Agent {
+            models,
+            prompts,
+            session,
+            config,
+            locks: None,
+            reporter: Arc::new(ConsoleReporter::default()),
+            workdir: PathBuf::from("."),
+            images: Vec::new(),
+            mcp_tools: Vec::new(),
+            interrupt: Interrupt::default(),
+            redactor,
+        }
+    }
+
+    /// Makes every write go through the shared file locks first.
+    pub fn with_locks(mut self, locks: FileLocks, owner: usize) -> Self {
+        self.locks = Some((locks, owner));
+        self
+    }
+
+    /// Runs every tool inside `workdir` instead of the current directory.
+    pub fn with_workdir(mut self, workdir: PathBuf) -> Self {
+        self.workdir = workdir;
+        self
+    }
+
+    /// Sends `images` along with the first request of the run.
+    pub fn with_images(mut self, images: Vec<Image>) -> Self {
+        self.images = images;
+        self
+    }
+
+    pub fn with_mcp_tools(mut self, tools: Vec<McpTool>) -> Self {
+        self.mcp_tools = tools;
+        self
+    }
+
+    /// Lets Ctrl-C cancel the model request or tool in flight.
+    pub fn with_interrupt(mut self, interrupt: Interrupt) -> Self {
+        self.interrupt = interrupt;
+        self
+    }
+
+    /// The handle a surface presses when Ctrl-C reaches it as input.
+    pub fn interrupt(&self) -> &Interrupt {
+        &self.interrupt
+    }
+
+    /// Sends progress somewhere other than plain stdout (e.g. the TUI).
+    pub fn with_reporter(mut self, reporter: Arc<dyn Reporter>) -> Self {
+        self.reporter = reporter;
+        self
+    }
+
+    /// Swaps the reporter of a running agent; returns the previous one.
+    pub fn set_reporter(&mut self, reporter: Arc<dyn Reporter>) -> Arc<dyn Reporter> {
+        std::mem::replace(&mut self.reporter, reporter)
+    }
+
+    pub fn report(&self, event: AgentEvent) {
+        self.reporter.report(event);
+    }
+
+    fn info(&self, text: &str) {
+        self.report(AgentEvent::Info { text: text.to_string() });
+    }
+
+    /// Purely reactive mode: act on the task until the model calls `finish`.
+    /// Returns whether it finished and the work was verified.
+    pub async fn run(&mut self) -> bool {
+        let task = self.session.task.clone();
+        let outcome = self.act(task, false, Role::Coder).await;
+        match outcome {
+            LoopOutcome::Exhausted => self.info("Max loops reached. Stopping."),
+            LoopOutcome::Interrupted => self.info("Stopped."),
+            _ => {}
+        }
+        self.save();
+        matches!(outcome, LoopOutcome::Finished)
+    }
+
+    /// Plan-and-execute mode: have the model write a step plan first, then
+    /// execute each step with the plan re-injected into the context.
+    /// Returns whether every step of the plan was completed.
+    pub async fn run_planned(&mut self) -> bool {
+        let planning_query = self.prompts.planning(&self.session.task);
+        if !matches!(self.act(planning_query, true, Role::Planner).await, LoopOutcome::PlanChanged) || self.session.plan.is_empty() {
+            self.info("\nNo plan was produced. Stopping.");
+            self.save();
+            return false;
+        }
+
+        // Plan changes since the current step was started; a model that keeps
+        // rewriting the plan would otherwise get fresh loops forever
+        let mut replans = 0;
+        let mut started_step = 0;
+        while let Some((number, step)) = self.session.plan.current() {
+            if number == started_step {
+                replans += 1;
+            } else {
+                (replans, started_step) = (0, number);
+            }
+            if replans > self.config.max_loops.value {
+                self.info(&format!("\nThe plan changed {} times without step {} getting done. Stopping.", replans, number));
+                break;
+            }
+            let step_query = self.prompts.step(number, &step.description);
+            match self.act(step_query, true, Role::Coder).await {
+                LoopOutcome::PlanChanged => continue,
+                // The model finished the whole task early, and it was verified
+                LoopOutcome::Finished => {
+                    self.save();
+                    return true;
+                }
+                LoopOutcome::Exhausted => self.info(&format!("\nMax loops reached on step {}. Stopping.", number)),
+                LoopOutcome::Interrupted => self.info(&format!("\nStopped on step {}.", number)),
+                LoopOutcome::Failed => {}
+            }
+            break;
+        }
+
+        let mut finished = self.session.plan.current().is_none();
+        if finished {
+            self.info("\nAll plan steps are done.");
+            if self.verifies() {
+                let query = "All plan steps are done. Call finish with a summary of the work.".to_string();
+                finished = matches!(self.act(query, false, Role::Coder).await, LoopOutcome::Finished);
+            }
+        }
+        self.save();
+        finished
+    }
+
+    /// Whether `finish` is checked by more than the model's word.
+    fn verifies(&self) -> bool {
+        !self.config.verify_checks.value.is_empty() || self.config.verify_review.value
+    }
+
+    /// Runs the `[verify]` checks and, with `review`, asks the model whether the
+    /// diff meets the original request. A failure goes back to the loop as the
+    /// `finish` observation.
+    async fn verify(&mut self, summary: &str) -> ToolResult {
+        let (passed, checks) = verify::run_checks(&self.config.verify_checks.value, &self.workdir).await;
+        if !passed {
+            return ToolResult {
+                tool_name: "finish".to_string(),
+                output: format!("Not finished: the checks failed. Fix them, then call finish again.\n{}", checks),
+                success: false,
+            };
+        }
+        if self.config.verify_review.value {
+            let (diff, _) = git::diff(&self.workdir, None, false).await;
+            let request = self.redactor.redact(&verify::review_request(&self.session.task, summary, &checks, &diff));
+            let model = self.models.pick(Role::Verifier);
+            let temperature = model.calculate_temperature(0, false);
+            match model.chat_completion(&self.prompts.reviewer(), &[], &request, &[], temperature).await {
+                Ok(completion) => {
+                    let mut turn = completion.usage;
+                    turn.tool = Some("verify".to_string());
+                    self.record_turn(turn);
+                    if let Err(reasons) = verify::parse_verdict(&completion.content) {
+                        return ToolResult {
+                            tool_name: "finish".to_string(),
+                            output: format!("Not finished: the review found the request is not met yet.\n{}", reasons),
+                            success: false,
+                        };
+                    }
+                }
+                // Don't hold the run hostage to the reviewer; the checks passed
+                Err(e) => self.report(AgentEvent::Error { text: format!("Review failed: {}", e) }),
+            }
+        }
+        ToolResult {
+            tool_name: "finish".to_string(),
+            output: format!("Finished: {}\n{}", summary, checks).trim_end().to_string(),
+            success: true,
+        }
+    }
+
+    /// The system prompt for the next turn, with the compacted session state
+    /// and the current plan filled in.
+    fn turn_prompt(&self) -> String {
+        let state = match &self.session.state {
+            Some(state) => format!("\n\n# SESSION STATE\nSummary of the earlier turns of this session:\n{}", state),
+            None => String::new(),
+        };
+        let plan = if self.session.plan.is_empty() { String::new() } else { format!("\n\n# CURRENT PLAN\n{}", self.session.plan.render()) };
+        self.prompts.system(&state, &plan)
+    }
+
+    /// Estimated tokens of the next request, without the answer.
+    fn request_tokens(&self, user_query: &str) -> usize {
+        let prompt = self.turn_prompt();
+        let history = self.session.history.iter().map(|m| m.content.as_str());
+        compaction::estimate_tokens([prompt.as_str()].into_iter().chain(history).chain([user_query]))
+    }
+
+    /// Whether the next request plus its answer passes `[limits] compact_at`
+    /// of the context of the models serving `role`.
+    fn over_budget(&self, user_query: &str, role: Role) -> bool {
+        let budget = self.models.context_tokens(role) as f32 * self.config.compact_at.value;
+        (self.request_tokens(user_query) + self.config.max_tokens.value as usize) as f32 > budget
+    }
+
+    /// Has the model summarize all but the last `KEEP_MESSAGES` messages into
+    /// the session state, which replaces them.
+    async fn compact(&mut self) {
+        let history = &self.session.history;
+        let split = compaction::compacted_len(history.len());
+        if split == 0 {
+            return;
+        }
+        let request = compaction::summary_request(&self.session.task, self.session.state.as_deref(), &history[..split]);
+        let before = self.request_tokens("");
+        let model = self.models.pick(Role::Summarizer);
+        let temperature = model.calculate_temperature(0, false);
+        let completion = match model.chat_completion(&self.prompts.summarizer(), &[], &request, &[], temperature).await {
+            Ok(completion) => completion,
+            Err(e) => {
+                self.report(AgentEvent::Error { text: format!("Compaction failed: {}", e) });
+                return;
+            }
+        };
+        self.session.history.drain(..split);
+        self.session.state = Some(completion.content.trim().to_string());
+        let mut turn = completion.usage;
+        turn.tool = Some("compact".to_string());
+        self.record_turn(turn);
+        self.info(&format!(
+            "Compacted {} messages into the session state (~{} -> ~{} tokens).",
+            split,
+            before,
+            self.request_tokens("")
+        ));
+        self.save();
+    }
+
+    async fn act(&mut self, query: String, yield_on_plan: bool, role: Role) -> LoopOutcome {
+        let mut user_query = query;
+        let mut loop_count = 0;
+
+        loop {
+            if self.over_budget(&user_query, role) {
+                let interrupt = self.interrupt.clone();
+                interrupt.cancellable(self.compact()).await;
+            }
+            // Picked per turn, so a busy profile's load shifts to an idle one
+            let model = self.models.pick(role);
+            let temperature = model.calculate_temperature(loop_count, false);
+            self.reporter.report(AgentEvent::Thinking {
+                temperature,
+                attempt: loop_count + 1,
+                model: model.model().to_string(),
+                context_tokens: model.context_tokens,
+            });
+            let images = std::mem::take(&mut self.images);
+            let prompt = self.turn_prompt();
+            let request = model.chat_completion(&prompt, &self.session.history, &user_query, &images, temperature);
+            let completion = match self.interrupt.cancellable(request).await {
+                Some(Ok(completion)) => completion,
+                Some(Err(e)) => {
+                    self.reporter.report(AgentEvent::Error { text: e.to_string() });
+                    return LoopOutcome::Failed;
+                }
+                None => {
+                    // The request never got through; ask again with the same query
+                    self.images = images;
+                    match self.follow_up().await {
+                        Some(instruction) => {
+                            user_query = format!("{}\n\n{}", user_query, instruction);
+                            continue;
+                        }
+                        None => return LoopOutcome::Interrupted,
+                    }
+                }
+            };
+            let response = completion.content;
+            let mut turn = completion.usage;
+            // The call itself is parsed unmasked, so a write of a secret the user asked for still works
+            let transcript = self.redactor.redact(&response);
+            self.session.history.push(Message { role: "user".to_string(), content: user_query.clone() });
+            self.session.history.push(Message { role: "assistant".to_string(), content: transcript.clone() });
+            self.reporter.report(AgentEvent::Assistant { text: transcript });
+
+            let parsed = parse_tool_call(&response, &self.config, &self.mcp_tools);
+            let Some(tool_call) = parsed.map(|call| self.redact_memory(self.expand_commit_message(call))) else {
+                // Prose is not a way out of the loop; only `finish` is
+                self.record_turn(turn);
+                self.info("\nNo tool call found.");
+                user_query = "Your last reply had no tool call. Reply with exactly one JSON tool call; call finish with a summary once the task is done.".to_string();
+                loop_count += 1;
+                if loop_count > self.config.max_loops.value {
+                    return LoopOutcome::Exhausted;
+                }
+                continue;
+            };
+
+            let finish = match &tool_call {
+                ToolCall::Finish { summary } => Some(summary.clone()),
+                _ => None,
+            };
+            let changes_plan = matches!(tool_call, ToolCall::UpdatePlan { .. } | ToolCall::CompleteStep { .. });
+            let args = self.redactor.redact_json(&tool_call.args());
+            self.reporter.report(AgentEvent::ToolCall { name: tool_call.name().to_string(), args });
+            // A refusal, or a write the reporter applied itself
+            let handled = self.check_policy(&tool_call).or_else(|| self.check_lock(&tool_call)).or_else(|| {
+                self.report_pending_write(&tool_call);
+                self.delegate_write(&tool_call).or_else(|| self.check_approval(&tool_call))
+            });
+            let tool_started = Instant::now();
+            let mut result = match (handled, finish) {
+                (Some(result), _) => result,
+                (None, Some(summary)) => {
+                    let interrupt = self.interrupt.clone();
+                    interrupt.cancellable(self.verify(&summary)).await.unwrap_or_else(|| cancelled("finish"))
+                }
+                (None, None) => {
+                    let tool_name = tool_call.name().to_string();
+                    let run = execute_tool(tool_call, &mut self.session.plan, &mut self.images, &self.workdir, &self.config);
+                    self.interrupt.cancellable(run).await.unwrap_or_else(|| cancelled(&tool_name))
+                }
+            };
+            result.output = self.redactor.redact(&result.output);
+            turn.tool = Some(result.tool_name.clone());
+            turn.tool_ms = tool_started.elapsed().as_millis() as u64;
+            self.reporter.report(AgentEvent::ToolResult {
+                name: result.tool_name.clone(),
+                output: result.output.clone(),
+                success: result.success,
+            });
+            self.record_turn(turn);
+
+            if result.tool_name == "finish" && result.success {
+                return LoopOutcome::Finished;
+            }
+            if changes_plan && result.success {
+                self.save();
+                if yield_on_plan {
+                    return LoopOutcome::PlanChanged;
+                }
+            }
+
+            // Feed the observation back into the next loop
+            user_query = format!("Observation from {}:\n{}", result.tool_name, result.output);
+            if self.interrupt.is_pressed() {
+                match self.follow_up().await {
+                    Some(instruction) => user_query = format!("{}\n\n{}", user_query, instruction),
+                    None => return LoopOutcome::Interrupted,
+                }
+            }
+            loop_count += 1;
+
+            if loop_count > self.config.max_loops.value {
+                return LoopOutcome::Exhausted;
+            }
+        }
+    }
+
+    /// After Ctrl-C: saves the session and asks the user how to go on;
+    /// `/compact` compacts the history and asks again. A second Ctrl-C exits,
+    /// here if it came before the save (see `Interrupt::saved`).
+    async fn follow_up(&mut self) -> Option<String> {
+        self.info("\nInterrupted.");
+        self.save();
+        self.interrupt.saved();
+        loop {
+            let instruction = self.reporter.follow_up()?;
+            if instruction != "/compact" {
+                self.interrupt.reset();
+                return Some(format!("The user interrupted you and said: {}", instruction));
+            }
+            if self.session.history.len() <= KEEP_MESSAGES {
+                self.info(&format!("Nothing to compact: the history is down to the last {} messages.", KEEP_MESSAGES));
+            } else {
+                self.compact().await;
+            }
+        }
+    }
+
+    /// Masks secrets in a memory before it is saved; later sessions put
+    /// memories in the prompt as they are.
+    fn redact_memory(&self, call: ToolCall) -> ToolCall {
+        let ToolCall::Remember { text, kind, tags, files } = call else {
+            return call;
+        };
+        let tags = tags.iter().map(|tag| self.redactor.redact(tag)).collect();
+        ToolCall::Remember { text: self.redactor.redact(&text), kind, tags, files }
+    }
+
+    /// Fills the `[git] commit_template` in, so the user approves the final message.
+    fn expand_commit_message(&self, call: ToolCall) -> ToolCall {
+        let ToolCall::GitCommit { message, files } = call else {
+            return call;
+        };
+        let message = self.config.commit_message(&message, &self.session.task, &self.session.id);
+        ToolCall::GitCommit { message, files }
+    }
+
+    /// Refuses tools disabled by the `[tools]` section of rumi.toml, and
+    /// `fetch_url` unless `[docs] fetch` is on.
+    fn check_policy(&self, call: &ToolCall) -> Option<ToolResult> {
+        let name = call.name();
+        let refusal = if self.config.is_tool_disabled(name) {
+            format!("The {} tool is disabled in this project.", name)
+        } else if matches!(call, ToolCall::FetchUrl { .. }) && !self.config.fetch_urls.value {
+            "fetch_url is off in this project; use docs_lookup for the local references.".to_string()
+        } else if let ToolCall::WriteFile { path, content } = call
+            && content.contains(redact::MARKER)
+            && !fs::read_to_string(self.workdir.join(path)).is_ok_and(|old| old.contains(redact::MARKER))
+        {
+            format!(
+                "The content contains a {}...] mask, which would replace a secret in the file. Edit the lines around it with run_shell (e.g. sed) instead of rewriting {}.",
+                redact::MARKER,
+                path
+            )
+        } else {
+            return None;
+        };
+        Some(ToolResult {
+            tool_name: name.to_string(),
+            output: refusal,
+            success: false,
+        })
+    }
+
+    /// Refuses a write when another sub-agent holds the lock on that path.
+    /// Sub-agents cannot commit: a commit would take the other units'
+    /// unfinished edits along.
+    fn check_lock(&self, call: &ToolCall) -> Option<ToolResult> {
+        let (locks, owner) = self.locks.as_ref()?;
+        let output = match call {
+            ToolCall::WriteFile { path, .. } => {
+                let holder = locks.claim(path, *owner).err()?;
+                format!("{} is being edited by unit {}. Leave it alone and work on your own target.", path, holder)
+            }
+            ToolCall::GitCommit { .. } => {
+                "Other units are editing the tree at the same time, so sub-agents do not commit; the user commits once the whole task is done.".to_string()
+            }
+            _ => return None,
+        };
+        Some(ToolResult { tool_name: call.name().to_string(), output, success: false })
+    }
+
+    /// Asks the user before running a tool listed under `[tools] approve`, or
+    /// one of an MCP server with `approve = true`. Commits always need approval.
+    /// So do the shell and project tools of sub-agents, which can write past
+    /// the file locks.
+    fn check_approval(&self, call: &ToolCall) -> Option<ToolResult> {
+        let name = call.name();
+        let required = self.config.needs_approval(name)
+            || matches!(call, ToolCall::GitCommit { .. })
+            || matches!(call, ToolCall::Mcp { tool, .. } if tool.approve)
+            || (self.locks.is_some() && matches!(call, ToolCall::RunShell { .. } | ToolCall::Custom { .. }));
+        if !required || self.reporter.approve(name, &self.redactor.redact(&call.summary())) {
+            return None;
+        }
+        Some(ToolResult {
+            tool_name: name.to_string(),
+            output: format!("The user did not approve {}. Do not retry it unchanged.", call.summary()),
+            success: false,
+        })
+    }
+
+    /// Hands a write to the reporter when it applies writes itself (an editor).
+    fn delegate_write(&self, call: &ToolCall) -> Option<ToolResult> {
+        let ToolCall::WriteFile { path, content } = call else {
+            return None;
+        };
+        let (output, success) = match self.reporter.apply_write(path, content)? {
+            Ok(output) => (output, true),
+            Err(output) => (output, false),
+        };
+        Some(ToolResult { tool_name: "write_file".to_string(), output, success })
+    }
+
+    /// Shows what a `write_file` is about to change, as a unified diff.
+    fn report_pending_write(&self, call: &ToolCall) {
+        let ToolCall::WriteFile { path, content } = call else {
+            return;
+        };
+        let old = fs::read_to_string(self.workdir.join(path)).unwrap_or_default();
+        let diff = TextDiff::from_lines(&old, content)
+            .unified_diff()
+            .context_radius(3)
+            .header(path, path)
+            .to_string();
+        self.reporter.report(AgentEvent::PendingWrite { path: path.clone(), diff: self.redactor.redact(&diff) });
+    }
+
+    fn record_turn(&mut self, turn: usage::TurnUsage) {
+        let context_tokens = turn.prompt_tokens + turn.completion_tokens;
+        self.session.usage.push(turn);
+        let totals = self.session.usage.totals();
+        self.reporter.report(AgentEvent::Usage {
+            status_line: self.session.usage.status_line(),
+            context_tokens,
+            session_tokens: totals.prompt_tokens + totals.completion_tokens,
+        });
+    }
+
+    /// Appends this session's usage to `.rumi/usage.jsonl` and returns the totals.
+    pub fn export_usage(&self) -> UsageTotals {
+        let totals = self.session.usage.totals();
+        let profiles = self
+            .models
+            .clients()
+            .iter()
+            .map(|client| (client, self.session.usage.for_profile(&client.profile)))
+            .filter(|(_, usage)| !usage.turns.is_empty())
+            .map(|(client, usage)| ProfileUsage {
+                profile: client.profile.clone(),
+                model: client.model().to_string(),
+                cost_per_1k_prompt: client.prices().0,
+                cost_per_1k_completion: client.prices().1,
+                totals: usage.totals(),
+            })
+            .collect();
+        let record = UsageRecord {
+            session: &self.session.id,
+            task: &self.session.task,
+            totals: totals.clone(),
+            profiles,
+            turns: &self.session.usage.turns,
+        };
+        if let Err(e) = usage::export(&record) {
+            eprintln!("Failed to export usage: {}", e);
+        }
+        totals
+    }
+
+    /// Exports usage and reports the end of the run with the session summary.
+    pub fn finish(&self, success: bool) -> UsageTotals {
+        let totals = self.export_usage();
+        self.report(AgentEvent::Done {
+            success,
+            session: self.session.path().display().to_string(),
+            summary: self.session.usage.summary(),
+            totals: totals.clone(),
+        });
+        totals
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = self.session.save() {
+            eprintln!("Failed to save session {}: {}", self.session.id, e);
+        }
+    }
+}
+
+fn cancelled(tool_name: &str) -> ToolResult {
+    ToolResult {
+        tool_name: tool_name.to_string(),
+        output: "Cancelled by the user (Ctrl-C).".to_string(),
+        success: false,
+    }
+}
+
+/// Simple parser for JSON in response. Names that are not built in are
+/// looked up among the `[[tools.custom]]` tools, then the MCP tools.
+fn parse_tool_call(response: &str, config: &Config, mcp_tools: &[McpTool]) -> Option<ToolCall> {
+    let json_start = response.find('{')?;
+    let json_end = response.rfind('}')?;
+    if json_end < json_start {
+        return None;
+    }
+    let mut value = serde_json::from_str::<serde_json::Value>(&response[json_start..json_end + 1]).ok()?;
+    // Models often drop "args" for tools that take none, e.g. {"tool": "git_status"}
+    if let Some(call) = value.as_object_mut()
+        && !call.contains_key("args")
+    {
+        call.insert("args".to_string(), serde_json::json!({}));
+    }
+    let name = value["tool"].as_str().unwrap_or_default().to_string();
+    serde_json::from_value::<ToolCall>(value.clone()).ok().or_else(|| {
+        let args = value["args"].take();
+        if let Some(tool) = config.custom_tool(&name) {
+            return Some(ToolCall::Custom { tool: tool.clone(), args });
+        }
+        let tool = mcp_tools.iter().find(|t| t.name == name)?.clone();
+        Some(ToolCall::Mcp { tool, args })
+    })
+}
@@ -0,0 +1,50 @@
+use crate::tasks::{ANALYZER_CONFIG, ANALYZER_PREFIX, ANALYZER_STATE};
+use efficiency_analyzer::analysis::{self, AnalysisConfig};
+use efficiency_analyzer::state::AnalyzerState;
+use std::fs;
+use std::path::Path;
+
+/// The analyzer's metrics for one file: LOC, nesting, drag against
+/// `drag_target`, the hotspot and dependencies. `path` is relative to `root`.
+pub fn analyze_file(root: &Path, path: &str) -> (String, bool) {
+    let config = match AnalysisConfig::load_from(ANALYZER_CONFIG) {
+        Ok(config) => config,
+        Err(e) => return (format!("Failed to load {}: {}", ANALYZER_CONFIG, e), false),
+    };
+    let content = match fs::read_to_string(root.join(path)) {
+        Ok(content) => content,
+        Err(e) => return (format!("Error reading file: {}", e), false),
+    };
+
+    let driver = analysis::driver_for(Path::new(path));
+    let metrics = analysis::analyze(driver, &content, &config.dictionary(driver));
+    // Files the agent failed on before weigh more, as they do in the scan
+    let multiplier = AnalyzerState::load_from(ANALYZER_STATE).get_drag_multiplier(&format!("{}{}", ANALYZER_PREFIX, path));
+    let drag = analysis::drag(&metrics, &config.drag, path) * multiplier;
+    let target = config.drag.drag_target;
+
+    let mut out = format!(
+        "{} ({})\nLOC: {}  logic: {}  max nesting: {}  state: {}\n",
+        path, driver, metrics.loc, metrics.logic_count, metrics.max_nesting, metrics.state_count
+    );
+    let verdict = if drag <= target { "under target".to_string() } else { format!("over target by {:.2}", drag - target) };
+    out.push_str(&format!("Drag: {:.2} (target {:.2}, {})", drag, target, verdict));
+    if multiplier != 1.0 {
+        out.push_str(&format!(", x{:.2} for past failures", multiplier));
+    }
+    out.push('\n');
+    match (&metrics.hotspot_symbol, metrics.hotspot_lines) {
+        (symbol, Some((start, end))) => out.push_str(&format!(
+            "Hotspot: L{}-{} {}{}\n",
+            start,
+            end,
+            symbol.as_deref().unwrap_or("block"),
+            metrics.hotspot_reason.as_ref().map(|r| format!(" - {}", r)).unwrap_or_default()
+        )),
+        (_, None) => out.push_str("Hotspot: none\n"),
+    }
+    if !metrics.dependencies.is_empty() {
+        out.push_str(&format!("Dependencies ({}): {}\n", metrics.dependencies.len(), metrics.dependencies.join(", ")));
+    }
+    (out, true)
+}
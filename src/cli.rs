@@ -0,0 +1,144 @@
+/// A parsed command line: what to do, plus config overrides from flags.
+pub struct Invocation {
+    pub command: Command,
+    pub output: Output,
+    /// `(config key, value, flag)` triples, the highest-precedence config layer.
+    pub overrides: Vec<(String, String, String)>,
+}
+
+/// What rumi was asked to do on the command line.
+pub enum Command {
+    /// `rumi [--plan] [--image PATH]... <task...>`: run the agent on a free-form
+    /// task, showing it the images with the first request.
+    Run { task: Option<String>, plan: bool, images: Vec<String> },
+    /// `rumi tasks list`: show the analyzer tasks under `tasks/`.
+    TasksList,
+    /// `rumi tasks run <id> [--plan] [--jobs N]`: run the agent on an analyzer task.
+    /// With more than one job, each target gets its own sub-agent (and `--tui` is ignored).
+    TasksRun { id: String, plan: bool, jobs: usize },
+    /// `rumi config show`: print the merged config and where each value came from.
+    ConfigShow,
+    /// `rumi doctor`: check that the inference server is up and serves the configured models.
+    Doctor,
+    /// `rumi serve`: run tasks for an editor over JSON-RPC on stdio.
+    Serve,
+    /// `rumi session merge <id>`: merge an isolated session's branch and drop its worktree.
+    SessionMerge { id: String },
+    /// `rumi session discard <id>`: drop an isolated session's branch and worktree.
+    SessionDiscard { id: String },
+}
+
+/// How progress is shown.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Output {
+    /// Plain text banners on stdout.
+    Text,
+    /// `--tui`: the full-screen terminal UI.
+    Tui,
+    /// `--output json`: one JSON event per line, for editors and CI.
+    /// With `--interactive`, approval and follow-up answers are read from stdin; otherwise they are denied.
+    Json { interactive: bool },
+}
+
+/// Flags that override a config key.
+const CONFIG_FLAGS: &[(&str, &str)] = &[
+    ("--api-url", "backend.api_url"),
+    ("--model", "backend.model"),
+    ("--max-tokens", "limits.max_tokens"),
+    ("--max-loops", "limits.max_loops"),
+    ("--map", "project.map"),
+];
+
+pub const USAGE: &str = "Usage:
+  rumi-cli [--plan] [--image PATH]... [task...]
+  rumi-cli tasks list
+  rumi-cli tasks run <id> [--plan] [--jobs N]
+  rumi-cli config show
+  rumi-cli doctor  (check the inference server and the configured models)
+  rumi-cli serve  (JSON-RPC on stdio, for editor plugins)
+  rumi-cli session merge|discard <id>  (finish an --isolate session)
+
+Output flags:
+  --tui  full-screen terminal UI
+  --output text|json  --interactive  (json: read approvals and follow-ups from stdin)
+
+Config flags (override rumi.toml and env):
+  --api-url URL  --model NAME  --max-tokens N  --max-loops N  --map PATH
+  --isolate  (run in a git worktree on branch rumi/<session-id>)";
+
+pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Invocation, String> {
+    let mut plan = false;
+    let mut tui = false;
+    let mut json = false;
+    let mut interactive = false;
+    let mut jobs = 1;
+    let mut images = Vec::new();
+    let mut words = Vec::new();
+    let mut overrides = Vec::new();
+    while let Some(arg) = args.next() {
+        if let Some((flag, key)) = CONFIG_FLAGS.iter().find(|(flag, _)| *flag == arg) {
+            let value = args.next().ok_or_else(|| format!("{} needs a value.\n\n{}", flag, USAGE))?;
+            overrides.push((key.to_string(), value, flag.to_string()));
+            continue;
+        }
+        match arg.as_str() {
+            "--plan" => plan = true,
+            "--isolate" => overrides.push(("git.isolate".to_string(), "true".to_string(), arg.clone())),
+            "--tui" => tui = true,
+            "--interactive" => interactive = true,
+            "--output" => {
+                json = match args.next().as_deref() {
+                    Some("json") => true,
+                    Some("text") => false,
+                    _ => return Err(format!("--output needs text or json.\n\n{}", USAGE)),
+                };
+            }
+            "--image" => images.push(args.next().ok_or_else(|| format!("--image needs a path.\n\n{}", USAGE))?),
+            "--jobs" => {
+                jobs = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| format!("--jobs needs a positive number.\n\n{}", USAGE))?;
+            }
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if arg.starts_with("--") => return Err(format!("Unknown flag: {}\n\n{}", arg, USAGE)),
+            _ => words.push(arg),
+        }
+    }
+
+    let command = match words.first().map(String::as_str) {
+        Some("tasks") => match words.get(1).map(String::as_str) {
+            Some("list") => Ok(Command::TasksList),
+            Some("run") => match words.get(2) {
+                Some(id) => Ok(Command::TasksRun { id: id.clone(), plan, jobs }),
+                None => Err(format!("Missing task id.\n\n{}", USAGE)),
+            },
+            _ => Err(USAGE.to_string()),
+        },
+        Some("config") => match words.get(1).map(String::as_str) {
+            Some("show") => Ok(Command::ConfigShow),
+            _ => Err(USAGE.to_string()),
+        },
+        Some("serve") if words.len() == 1 => Ok(Command::Serve),
+        Some("doctor") if words.len() == 1 => Ok(Command::Doctor),
+        Some("session") => match (words.get(1).map(String::as_str), words.get(2)) {
+            (Some("merge"), Some(id)) => Ok(Command::SessionMerge { id: id.clone() }),
+            (Some("discard"), Some(id)) => Ok(Command::SessionDiscard { id: id.clone() }),
+            _ => Err(format!("Expected `session merge <id>` or `session discard <id>`.\n\n{}", USAGE)),
+        },
+        Some(_) => Ok(Command::Run {
+            task: Some(words.join(" ")),
+            plan,
+            images,
+        }),
+        None => Ok(Command::Run { task: None, plan, images }),
+    }?;
+    let output = match (tui, json) {
+        (true, true) => return Err(format!("--tui and --output json cannot be combined.\n\n{}", USAGE)),
+        (true, false) => Output::Tui,
+        (false, true) => Output::Json { interactive },
+        (false, false) => Output::Text,
+    };
+    Ok(Invocation { command, output, overrides })
+}
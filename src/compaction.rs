@@ -0,0 +1,79 @@
+use crate::llm::Message;
+
+/// Messages kept verbatim when the rest is compacted: the last two turns.
+pub const KEEP_MESSAGES: usize = 4;
+/// Longest message quoted to the summarizer; tool output beyond this is noise.
+const MAX_QUOTED_CHARS: usize = 2_000;
+
+/// Rough token count, the same 4 characters per token the client estimates with.
+pub fn estimate_tokens<'a>(texts: impl IntoIterator<Item = &'a str>) -> usize {
+    texts.into_iter().map(str::len).sum::<usize>() / 4
+}
+
+/// How many of the oldest `messages` a compaction summarizes; the last
+/// `KEEP_MESSAGES` stay as they are.
+pub fn compacted_len(messages: usize) -> usize {
+    messages.saturating_sub(KEEP_MESSAGES)
+}
+
+/// The request that turns `state` (an earlier summary) and `older` messages
+/// into a new session state.
+pub fn summary_request(task: &str, state: Option<&str>, older: &[Message]) -> String {
+    let mut request = format!("TASK: {}\n\n", task);
+    if let Some(state) = state {
+        request.push_str(&format!("SESSION STATE SO FAR:\n{}\n\n", state));
+    }
+    request.push_str("HISTORY:\n");
+    for message in older {
+        let content = match message.content.char_indices().nth(MAX_QUOTED_CHARS) {
+            Some((cut, _)) => format!("{} ... (cut)", &message.content[..cut]),
+            None => message.content.clone(),
+        };
+        request.push_str(&format!("[{}]\n{}\n\n", message.role, content.trim()));
+    }
+    request.push_str(
+        "Write the updated session state with exactly these sections:\n\
+         GOAL: the task and what finished looks like\n\
+         DECISIONS: choices made and why, one per line\n\
+         FILES TOUCHED: path - what changed or was learned, one per line\n\
+         OPEN PROBLEMS: errors, failing checks and unanswered questions, one per line\n\
+         NEXT STEP: what to do next",
+    );
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> Message {
+        Message { role: role.to_string(), content: content.to_string() }
+    }
+
+    #[test]
+    fn test_summary_request() {
+        let long = format!("{}{}", "é".repeat(MAX_QUOTED_CHARS), "tail");
+        let older = [message("user", "  Fix the parser  "), message("assistant", &long)];
+        let request = summary_request("fix parsing", Some("GOAL: parse"), &older);
+
+        assert!(request.starts_with("TASK: fix parsing\n\nSESSION STATE SO FAR:\nGOAL: parse\n\nHISTORY:\n[user]\nFix the parser\n\n"));
+        let quoted = format!("[assistant]\n{} ... (cut)\n\n", "é".repeat(MAX_QUOTED_CHARS));
+        assert!(request.contains(&quoted));
+        assert!(!request.contains("tail"));
+        assert!(request.ends_with("NEXT STEP: what to do next"));
+
+        let first = summary_request("fix parsing", None, &older[..1]);
+        assert!(first.starts_with("TASK: fix parsing\n\nHISTORY:\n"));
+    }
+
+    #[test]
+    fn test_the_last_messages_survive() {
+        assert_eq!(compacted_len(KEEP_MESSAGES), 0);
+        assert_eq!(compacted_len(1), 0);
+
+        let mut history: Vec<Message> = (0..7).map(|i| message("user", &i.to_string())).collect();
+        history.drain(..compacted_len(history.len()));
+        let kept: Vec<&str> = history.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(kept, ["3", "4", "5", "6"]);
+    }
+}
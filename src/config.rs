@@ -0,0 +1,642 @@
+use crate::custom_tools::CustomTool;
+use crate::mcp::McpServerConfig;
+use crate::models::{ModelProfile, Role, DEFAULT_PROFILE};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PROJECT_CONFIG: &str = "rumi.toml";
+
+/// Where a setting's value came from.
+#[derive(Debug, Clone)]
+pub enum Source {
+    Default,
+    User(PathBuf),
+    Project(PathBuf),
+    Env(&'static str),
+    Cli(String),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::User(path) => write!(f, "user {}", path.display()),
+            Source::Project(path) => write!(f, "project {}", path.display()),
+            Source::Env(var) => write!(f, "env {}", var),
+            Source::Cli(flag) => write!(f, "cli {}", flag),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Setting<T> {
+    fn default(value: T) -> Self {
+        Setting { value, source: Source::Default }
+    }
+
+    fn set(&mut self, value: T, source: &Source) {
+        self.value = value;
+        self.source = source.clone();
+    }
+}
+
+/// The merged configuration: built-in defaults, then `~/.config/rumi/rumi.toml`,
+/// then `./rumi.toml`, then environment variables, then CLI flags.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub api_url: Setting<String>,
+    pub model: Setting<String>,
+    /// Further models, from `[[backend.profiles]]`; `[backend]` itself is the `default` profile.
+    pub model_profiles: Vec<Setting<ModelProfile>>,
+    /// Profiles serving each role, tried in order; unrouted roles use `default`.
+    pub roles: BTreeMap<Role, Setting<Vec<String>>>,
+    pub base_temperature: Setting<f32>,
+    pub max_temperature: Setting<f32>,
+    /// Price per 1k prompt / completion tokens, for cost accounting (0 for local models).
+    pub prompt_cost: Setting<f64>,
+    pub completion_cost: Setting<f64>,
+    pub max_tokens: Setting<u32>,
+    pub max_loops: Setting<u32>,
+    /// Context window of the model, shown as the token budget in the TUI.
+    pub context_tokens: Setting<u32>,
+    /// Share of `context_tokens` at which older turns are compacted into a summary.
+    pub compact_at: Setting<f32>,
+    pub disabled_tools: Setting<Vec<String>>,
+    /// Tools that only run after the user approves each call.
+    pub approve_tools: Setting<Vec<String>>,
+    /// Project tools from `[[tools.custom]]`; a later layer replaces a tool of the same name.
+    pub custom_tools: Vec<Setting<CustomTool>>,
+    /// MCP servers from `[[mcp.servers]]`; a later layer replaces a server of the same name.
+    pub mcp_servers: Vec<Setting<McpServerConfig>>,
+    pub map_path: Setting<String>,
+    /// Message for `git_commit`; `{message}`, `{task}` and `{session}` are filled in.
+    pub commit_template: Setting<String>,
+    /// Run each session in its own worktree on a `rumi/<session-id>` branch.
+    pub isolate: Setting<bool>,
+    /// Directories of markdown references searched by `docs_lookup`.
+    pub docs_dirs: Setting<Vec<String>>,
+    /// Offer `fetch_url`; fetched pages are cached for offline use either way.
+    pub fetch_urls: Setting<bool>,
+    /// Commands that must pass before `finish` is accepted.
+    pub verify_checks: Setting<Vec<String>>,
+    /// Also ask the model whether the diff meets the request before finishing.
+    pub verify_review: Setting<bool>,
+    /// Mask secrets in tool output and transcripts (see `redact`).
+    pub redact: Setting<bool>,
+    /// Regexes masked on top of the built-in secret patterns.
+    pub redact_patterns: Setting<Vec<String>>,
+    /// Extra text appended to the system prompt; fragments from every layer are kept.
+    pub prompt_fragments: Vec<Setting<String>>,
+    /// Project rules appended to the RULES section; rules from every layer are kept.
+    pub rules: Vec<Setting<String>>,
+}
+
+// Layout of a rumi.toml file. Every key is optional so that layers can be partial.
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+    backend: Option<BackendSection>,
+    limits: Option<LimitsSection>,
+    tools: Option<ToolsSection>,
+    project: Option<ProjectSection>,
+    git: Option<GitSection>,
+    prompt: Option<PromptSection>,
+    mcp: Option<McpSection>,
+    docs: Option<DocsSection>,
+    verify: Option<VerifySection>,
+    redact: Option<RedactSection>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BackendSection {
+    api_url: Option<String>,
+    model: Option<String>,
+    base_temperature: Option<f32>,
+    max_temperature: Option<f32>,
+    cost_per_1k_prompt: Option<f64>,
+    cost_per_1k_completion: Option<f64>,
+    profiles: Option<Vec<ModelProfile>>,
+    roles: Option<BTreeMap<Role, Vec<String>>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LimitsSection {
+    max_tokens: Option<u32>,
+    max_loops: Option<u32>,
+    context_tokens: Option<u32>,
+    compact_at: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ToolsSection {
+    disabled: Option<Vec<String>>,
+    approve: Option<Vec<String>>,
+    custom: Option<Vec<CustomTool>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectSection {
+    map: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GitSection {
+    commit_template: Option<String>,
+    isolate: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DocsSection {
+    dirs: Option<Vec<String>>,
+    fetch: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VerifySection {
+    checks: Option<Vec<String>>,
+    review: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RedactSection {
+    enabled: Option<bool>,
+    patterns: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct McpSection {
+    servers: Option<Vec<McpServerConfig>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PromptSection {
+    fragments: Option<Vec<String>>,
+    rules: Option<Vec<String>>,
+}
+
+/// Environment variables and the config keys they set.
+pub const ENV_VARS: &[(&str, &str)] = &[
+    ("VLLM_API_URL", "backend.api_url"),
+    ("MODEL_NAME", "backend.model"),
+    ("BASE_TEMPERATURE", "backend.base_temperature"),
+    ("MAX_TEMPERATURE", "backend.max_temperature"),
+    ("RUMI_MAX_TOKENS", "limits.max_tokens"),
+    ("RUMI_MAX_LOOPS", "limits.max_loops"),
+    ("RUMI_CONTEXT_TOKENS", "limits.context_tokens"),
+    ("RUMI_COMPACT_AT", "limits.compact_at"),
+    ("RUMI_ISOLATE", "git.isolate"),
+    ("RUMI_FETCH", "docs.fetch"),
+    ("RUMI_VERIFY_REVIEW", "verify.review"),
+    ("RUMI_REDACT", "redact.enabled"),
+];
+
+impl Default for Config {
+    fn default() -> Self {
+        // Matches the server started by start_vllm.sh
+        Config {
+            api_url: Setting::default("http://localhost:8000/v1".to_string()),
+            model: Setting::default("qwen3-4b".to_string()),
+            model_profiles: Vec::new(),
+            roles: BTreeMap::new(),
+            base_temperature: Setting::default(0.7),
+            max_temperature: Setting::default(1.2),
+            prompt_cost: Setting::default(0.0),
+            completion_cost: Setting::default(0.0),
+            max_tokens: Setting::default(2048),
+            max_loops: Setting::default(5),
+            context_tokens: Setting::default(24576),
+            compact_at: Setting::default(0.75),
+            disabled_tools: Setting::default(Vec::new()),
+            approve_tools: Setting::default(Vec::new()),
+            custom_tools: Vec::new(),
+            mcp_servers: Vec::new(),
+            map_path: Setting::default("MAP.md".to_string()),
+            commit_template: Setting::default("{message}".to_string()),
+            isolate: Setting::default(false),
+            docs_dirs: Setting::default(vec!["docs".to_string()]),
+            fetch_urls: Setting::default(false),
+            verify_checks: Setting::default(Vec::new()),
+            verify_review: Setting::default(false),
+            redact: Setting::default(true),
+            redact_patterns: Setting::default(Vec::new()),
+            prompt_fragments: Vec::new(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads every layer. `overrides` are `(key, value, flag)` triples from the command line.
+    pub fn load(overrides: &[(String, String, String)]) -> Result<Self, String> {
+        dotenvy::dotenv().ok();
+        let mut config = Config::default();
+
+        if let Some(path) = user_config_path().filter(|p| p.exists()) {
+            config.apply_file(&path, Source::User(path.clone()))?;
+        }
+        let project = PathBuf::from(PROJECT_CONFIG);
+        if project.exists() {
+            config.apply_file(&project, Source::Project(project.clone()))?;
+        }
+        for (var, key) in ENV_VARS {
+            if let Ok(value) = env::var(var) {
+                config.apply_value(key, &value, &Source::Env(var))?;
+            }
+        }
+        for (key, value, flag) in overrides {
+            config.apply_value(key, value, &Source::Cli(flag.clone()))?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks that need every layer applied: profiles and routes may come
+    /// from different files, and a value set in one layer may be fixed by a later one.
+    fn validate(&self) -> Result<(), String> {
+        for (role, names) in &self.roles {
+            for name in &names.value {
+                if name != DEFAULT_PROFILE && !self.model_profiles.iter().any(|p| p.value.name == *name) {
+                    return Err(format!("Unknown model profile {:?} in backend.roles.{} ({})", name, role.name(), names.source));
+                }
+            }
+        }
+        // `[backend]` is the default profile; the others fall back to it
+        let backend = (DEFAULT_PROFILE, None, None, &self.max_temperature.source);
+        let profiles = self.model_profiles.iter().map(|p| (p.value.name.as_str(), p.value.base_temperature, p.value.max_temperature, &p.source));
+        for (name, base, max, source) in [backend].into_iter().chain(profiles) {
+            let (base, max) = (base.unwrap_or(self.base_temperature.value), max.unwrap_or(self.max_temperature.value));
+            if !(0.0..=2.0).contains(&base) || !(0.0..=2.0).contains(&max) || base > max {
+                return Err(format!(
+                    "Invalid temperatures of the {:?} profile ({}): base {} and max {} must be between 0 and 2, base at most max",
+                    name, source, base, max
+                ));
+            }
+        }
+        let compact_at = &self.compact_at;
+        if !(compact_at.value > 0.0 && compact_at.value <= 1.0) {
+            return Err(format!(
+                "Invalid limits.compact_at ({}): {} is not a share of the context between 0 and 1",
+                compact_at.source, compact_at.value
+            ));
+        }
+        Ok(())
+    }
+
+    fn apply_file(&mut self, path: &Path, source: Source) -> Result<(), String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let file: FileConfig = toml::from_str(&content).map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+
+        if let Some(backend) = file.backend {
+            if let Some(v) = backend.api_url {
+                self.api_url.set(v, &source);
+            }
+            if let Some(v) = backend.model {
+                self.model.set(v, &source);
+            }
+            if let Some(v) = backend.base_temperature {
+                self.base_temperature.set(v, &source);
+            }
+            if let Some(v) = backend.max_temperature {
+                self.max_temperature.set(v, &source);
+            }
+            if let Some(v) = backend.cost_per_1k_prompt {
+                self.prompt_cost.set(v, &source);
+            }
+            if let Some(v) = backend.cost_per_1k_completion {
+                self.completion_cost.set(v, &source);
+            }
+            for profile in backend.profiles.unwrap_or_default() {
+                if profile.name == DEFAULT_PROFILE {
+                    return Err(format!("Invalid {}: the {:?} profile is [backend] itself", path.display(), DEFAULT_PROFILE));
+                }
+                self.model_profiles.retain(|p| p.value.name != profile.name);
+                self.model_profiles.push(Setting { value: profile, source: source.clone() });
+            }
+            for (role, names) in backend.roles.unwrap_or_default() {
+                self.roles.insert(role, Setting { value: names, source: source.clone() });
+            }
+        }
+        if let Some(limits) = file.limits {
+            if let Some(v) = limits.max_tokens {
+                self.max_tokens.set(v, &source);
+            }
+            if let Some(v) = limits.max_loops {
+                self.max_loops.set(v, &source);
+            }
+            if let Some(v) = limits.context_tokens {
+                self.context_tokens.set(v, &source);
+            }
+            if let Some(v) = limits.compact_at {
+                self.compact_at.set(v, &source);
+            }
+        }
+        if let Some(tools) = file.tools {
+            if let Some(v) = tools.disabled {
+                self.disabled_tools.set(v, &source);
+            }
+            if let Some(v) = tools.approve {
+                self.approve_tools.set(v, &source);
+            }
+            for tool in tools.custom.unwrap_or_default() {
+                tool.validate().map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+                self.custom_tools.retain(|t| t.value.name != tool.name);
+                self.custom_tools.push(Setting { value: tool, source: source.clone() });
+            }
+        }
+        if let Some(v) = file.project.and_then(|p| p.map) {
+            self.map_path.set(v, &source);
+        }
+        if let Some(git) = file.git {
+            if let Some(v) = git.commit_template {
+                self.commit_template.set(v, &source);
+            }
+            if let Some(v) = git.isolate {
+                self.isolate.set(v, &source);
+            }
+        }
+        if let Some(docs) = file.docs {
+            if let Some(v) = docs.dirs {
+                self.docs_dirs.set(v, &source);
+            }
+            if let Some(v) = docs.fetch {
+                self.fetch_urls.set(v, &source);
+            }
+        }
+        if let Some(verify) = file.verify {
+            if let Some(v) = verify.checks {
+                self.verify_checks.set(v, &source);
+            }
+            if let Some(v) = verify.review {
+                self.verify_review.set(v, &source);
+            }
+        }
+        if let Some(redact) = file.redact {
+            if let Some(v) = redact.enabled {
+                self.redact.set(v, &source);
+            }
+            if let Some(v) = redact.patterns {
+                for pattern in &v {
+                    crate::redact::validate_pattern(pattern).map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+                }
+                self.redact_patterns.set(v, &source);
+            }
+        }
+        for server in file.mcp.and_then(|m| m.servers).unwrap_or_default() {
+            self.mcp_servers.retain(|s| s.value.name != server.name);
+            self.mcp_servers.push(Setting { value: server, source: source.clone() });
+        }
+        if let Some(prompt) = file.prompt {
+            for fragment in prompt.fragments.unwrap_or_default() {
+                self.prompt_fragments.push(Setting { value: fragment, source: source.clone() });
+            }
+            for rule in prompt.rules.unwrap_or_default() {
+                self.rules.push(Setting { value: rule, source: source.clone() });
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets a single scalar key from an env var or CLI flag.
+    fn apply_value(&mut self, key: &str, value: &str, source: &Source) -> Result<(), String> {
+        let invalid = || format!("Invalid value for {} ({}): {}", key, source, value);
+        match key {
+            "backend.api_url" => self.api_url.set(value.to_string(), source),
+            "backend.model" => self.model.set(value.to_string(), source),
+            "backend.base_temperature" => self.base_temperature.set(value.parse().map_err(|_| invalid())?, source),
+            "backend.max_temperature" => self.max_temperature.set(value.parse().map_err(|_| invalid())?, source),
+            "limits.max_tokens" => self.max_tokens.set(value.parse().map_err(|_| invalid())?, source),
+            "limits.max_loops" => self.max_loops.set(value.parse().map_err(|_| invalid())?, source),
+            "limits.context_tokens" => self.context_tokens.set(value.parse().map_err(|_| invalid())?, source),
+            "limits.compact_at" => self.compact_at.set(value.parse().map_err(|_| invalid())?, source),
+            "project.map" => self.map_path.set(value.to_string(), source),
+            "git.commit_template" => self.commit_template.set(value.to_string(), source),
+            "git.isolate" => self.isolate.set(value.parse().map_err(|_| invalid())?, source),
+            "docs.fetch" => self.fetch_urls.set(value.parse().map_err(|_| invalid())?, source),
+            "verify.review" => self.verify_review.set(value.parse().map_err(|_| invalid())?, source),
+            "redact.enabled" => self.redact.set(value.parse().map_err(|_| invalid())?, source),
+            _ => return Err(format!("Unknown config key: {}", key)),
+        }
+        Ok(())
+    }
+
+    /// `(prompt, completion)` cost per 1k tokens.
+    pub fn prices(&self) -> (f64, f64) {
+        (self.prompt_cost.value, self.completion_cost.value)
+    }
+
+    /// Fills in `[git] commit_template`.
+    pub fn commit_message(&self, message: &str, task: &str, session: &str) -> String {
+        let values = [("task", task), ("session", session), ("message", message.trim())];
+        // One pass over the template, so a `{name}` inside a value is never expanded
+        let mut out = String::new();
+        let mut rest = self.commit_template.value.as_str();
+        while let Some(open) = rest.find('{') {
+            out.push_str(&rest[..open]);
+            let value = rest[open..]
+                .find('}')
+                .and_then(|close| Some((close, values.iter().find(|(name, _)| *name == &rest[open + 1..open + close])?.1)));
+            match value {
+                Some((close, value)) => {
+                    out.push_str(value);
+                    rest = &rest[open + close + 1..];
+                }
+                None => {
+                    out.push('{');
+                    rest = &rest[open + 1..];
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+
+    pub fn is_tool_disabled(&self, tool: &str) -> bool {
+        self.disabled_tools.value.iter().any(|t| t == tool)
+    }
+
+    pub fn needs_approval(&self, tool: &str) -> bool {
+        self.approve_tools.value.iter().any(|t| t == tool)
+    }
+
+    pub fn custom_tool(&self, name: &str) -> Option<&CustomTool> {
+        self.custom_tools.iter().map(|t| &t.value).find(|t| t.name == name)
+    }
+
+    /// `rumi config show`: every merged value and the layer it came from.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut line = |key: &str, value: String, source: &Source| {
+            out.push_str(&format!("{:<30} = {:<40} # {}\n", key, value, source));
+        };
+        line("backend.api_url", format!("{:?}", self.api_url.value), &self.api_url.source);
+        line("backend.model", format!("{:?}", self.model.value), &self.model.source);
+        line("backend.base_temperature", self.base_temperature.value.to_string(), &self.base_temperature.source);
+        line("backend.max_temperature", self.max_temperature.value.to_string(), &self.max_temperature.source);
+        line("backend.cost_per_1k_prompt", self.prompt_cost.value.to_string(), &self.prompt_cost.source);
+        line("backend.cost_per_1k_completion", self.completion_cost.value.to_string(), &self.completion_cost.source);
+        for profile in &self.model_profiles {
+            let p = &profile.value;
+            let url = p.api_url.as_deref().unwrap_or(&self.api_url.value);
+            line(&format!("backend.profiles.{}", p.name), format!("{:?}", format!("{} @ {}", p.model, url)), &profile.source);
+        }
+        for (role, names) in &self.roles {
+            line(&format!("backend.roles.{}", role.name()), format!("{:?}", names.value), &names.source);
+        }
+        line("limits.max_tokens", self.max_tokens.value.to_string(), &self.max_tokens.source);
+        line("limits.max_loops", self.max_loops.value.to_string(), &self.max_loops.source);
+        line("limits.context_tokens", self.context_tokens.value.to_string(), &self.context_tokens.source);
+        line("limits.compact_at", self.compact_at.value.to_string(), &self.compact_at.source);
+        line("tools.disabled", format!("{:?}", self.disabled_tools.value), &self.disabled_tools.source);
+        line("tools.approve", format!("{:?}", self.approve_tools.value), &self.approve_tools.source);
+        for tool in &self.custom_tools {
+            line(&format!("tools.custom.{}", tool.value.name), format!("{:?}", tool.value.command), &tool.source);
+        }
+        for server in &self.mcp_servers {
+            let command = std::iter::once(&server.value.command).chain(&server.value.args).cloned().collect::<Vec<_>>().join(" ");
+            line(&format!("mcp.servers.{}", server.value.name), format!("{:?}", command), &server.source);
+        }
+        line("project.map", format!("{:?}", self.map_path.value), &self.map_path.source);
+        line("git.commit_template", format!("{:?}", self.commit_template.value), &self.commit_template.source);
+        line("git.isolate", self.isolate.value.to_string(), &self.isolate.source);
+        line("docs.dirs", format!("{:?}", self.docs_dirs.value), &self.docs_dirs.source);
+        line("docs.fetch", self.fetch_urls.value.to_string(), &self.fetch_urls.source);
+        line("verify.checks", format!("{:?}", self.verify_checks.value), &self.verify_checks.source);
+        line("verify.review", self.verify_review.value.to_string(), &self.verify_review.source);
+        line("redact.enabled", self.redact.value.to_string(), &self.redact.source);
+        line("redact.patterns", format!("{:?}", self.redact_patterns.value), &self.redact_patterns.source);
+        for (i, fragment) in self.prompt_fragments.iter().enumerate() {
+            line(&format!("prompt.fragments[{}]", i), format!("{:?}", fragment.value), &fragment.source);
+        }
+        for (i, rule) in self.rules.iter().enumerate() {
+            line(&format!("prompt.rules[{}]", i), format!("{:?}", rule.value), &rule.source);
+        }
+        out
+    }
+}
+
+/// `$XDG_CONFIG_HOME/rumi/rumi.toml`, falling back to `~/.config/rumi/rumi.toml`.
+fn user_config_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("rumi").join(PROJECT_CONFIG))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `content` to a temporary rumi.toml and applies it as `source`.
+    fn apply(config: &mut Config, name: &str, content: &str, source: fn(PathBuf) -> Source) -> Result<(), String> {
+        let path = env::temp_dir().join(format!("rumi-config-{}-{}.toml", std::process::id(), name));
+        fs::write(&path, content).unwrap();
+        let result = config.apply_file(&path, source(path.clone()));
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn test_later_layers_win() {
+        let mut config = Config::default();
+        let user = "[backend]\nmodel = \"user-model\"\n\n[limits]\nmax_loops = 9\ncontext_tokens = 8192\n\n[[backend.profiles]]\nname = \"big\"\nmodel = \"qwen3-32b\"\n";
+        apply(&mut config, "user", user, Source::User).unwrap();
+        let project = "[backend]\nmodel = \"project-model\"\n\n[[backend.profiles]]\nname = \"big\"\nmodel = \"qwen3-14b\"\n\n[prompt]\nrules = [\"Keep modules small.\"]\n";
+        apply(&mut config, "project", project, Source::Project).unwrap();
+        config.apply_value("limits.max_loops", "3", &Source::Env("RUMI_MAX_LOOPS")).unwrap();
+        config.apply_value("backend.model", "cli-model", &Source::Cli("--model".to_string())).unwrap();
+
+        assert_eq!(config.model.value, "cli-model");
+        assert!(matches!(config.model.source, Source::Cli(ref flag) if flag == "--model"));
+        assert_eq!(config.max_loops.value, 3);
+        assert!(matches!(config.max_loops.source, Source::Env("RUMI_MAX_LOOPS")));
+        assert_eq!(config.context_tokens.value, 8192);
+        assert!(matches!(config.context_tokens.source, Source::User(_)));
+        assert!(matches!(config.api_url.source, Source::Default));
+
+        // A profile of the same name replaces the earlier one; rules add up
+        assert_eq!(config.model_profiles.len(), 1);
+        assert_eq!(config.model_profiles[0].value.model, "qwen3-14b");
+        assert!(matches!(config.model_profiles[0].source, Source::Project(_)));
+        assert_eq!(config.rules.len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_values_are_rejected() {
+        let mut config = Config::default();
+        let env = Source::Env("RUMI_MAX_LOOPS");
+        assert_eq!(
+            config.apply_value("limits.max_loops", "many", &env).unwrap_err(),
+            "Invalid value for limits.max_loops (env RUMI_MAX_LOOPS): many"
+        );
+        assert_eq!(config.apply_value("limits.loops", "3", &env).unwrap_err(), "Unknown config key: limits.loops");
+        assert!(apply(&mut config, "typo", "[limits]\nmax_loop = 3\n", Source::Project).is_err());
+        assert!(apply(&mut config, "default", "[[backend.profiles]]\nname = \"default\"\nmodel = \"m\"\n", Source::Project).is_err());
+        assert!(apply(&mut config, "pattern", "[redact]\npatterns = [\"(unclosed\"]\n", Source::Project).is_err());
+        assert_eq!(config.max_loops.value, 5);
+    }
+
+    #[test]
+    fn test_compact_at_is_a_share() {
+        let mut config = Config::default();
+        assert!(config.validate().is_ok());
+        for value in ["0", "-0.5", "1.5", "75", "NaN"] {
+            config.apply_value("limits.compact_at", value, &Source::Env("RUMI_COMPACT_AT")).unwrap();
+            let error = config.validate().unwrap_err();
+            assert!(error.starts_with("Invalid limits.compact_at (env RUMI_COMPACT_AT): "), "{}", error);
+        }
+        config.apply_value("limits.compact_at", "1", &Source::Env("RUMI_COMPACT_AT")).unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_profile_temperatures_are_checked() {
+        let mut config = Config::default();
+        let profile = |name: &str, temperatures: &str| format!("[[backend.profiles]]\nname = \"{}\"\nmodel = \"m\"\n{}\n", name, temperatures);
+        apply(&mut config, "cool", &profile("cool", "base_temperature = 0.1\nmax_temperature = 0.3"), Source::Project).unwrap();
+        assert!(config.validate().is_ok());
+
+        apply(&mut config, "hot", &profile("hot", "max_temperature = 2.5"), Source::Project).unwrap();
+        let error = config.validate().unwrap_err();
+        assert!(error.starts_with("Invalid temperatures of the \"hot\" profile (project "), "{}", error);
+        assert!(error.ends_with("base 0.7 and max 2.5 must be between 0 and 2, base at most max"), "{}", error);
+
+        // The base falls back to `[backend]` and ends up above the profile's max
+        apply(&mut config, "hot", &profile("hot", "max_temperature = 0.5"), Source::Project).unwrap();
+        assert!(config.validate().unwrap_err().contains("base 0.7 and max 0.5"));
+        apply(&mut config, "hot", &profile("hot", "base_temperature = 0.2\nmax_temperature = 0.5"), Source::Project).unwrap();
+        assert!(config.validate().is_ok());
+
+        config.apply_value("backend.base_temperature", "-0.1", &Source::Env("BASE_TEMPERATURE")).unwrap();
+        assert!(config.validate().unwrap_err().starts_with("Invalid temperatures of the \"default\" profile"));
+    }
+
+    #[test]
+    fn test_commit_message_expands_once() {
+        let mut config = Config::default();
+        config.apply_value("git.commit_template", "{message}\n\nTask: {task} ({session}) {other}", &Source::Default).unwrap();
+        assert_eq!(
+            config.commit_message(" Fix parser\n", "rename {message} to {session}", "s1"),
+            "Fix parser\n\nTask: rename {message} to {session} (s1) {other}"
+        );
+    }
+}
@@ -0,0 +1,242 @@
+use crate::tools::ToolCall;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// A project tool declared under `[[tools.custom]]` in rumi.toml:
+///
+/// ```toml
+/// [[tools.custom]]
+/// name = "run_tests"
+/// description = "Run the test suite, optionally filtered by test name"
+/// command = "npm test -- {pattern}"
+/// args.pattern = { type = "string", description = "Test name filter", required = false }
+/// ```
+///
+/// Calls run through `sh -c` like `run_shell`, with every `{arg}` replaced by
+/// the shell-quoted value (or nothing when an optional arg is left out).
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CustomTool {
+    pub name: String,
+    pub description: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: BTreeMap<String, ArgSpec>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ArgSpec {
+    #[serde(rename = "type", default)]
+    pub kind: ArgType,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default = "default_required")]
+    pub required: bool,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArgType {
+    #[default]
+    String,
+    Integer,
+    Number,
+    Boolean,
+}
+
+impl ArgType {
+    fn name(self) -> &'static str {
+        match self {
+            ArgType::String => "string",
+            ArgType::Integer => "integer",
+            ArgType::Number => "number",
+            ArgType::Boolean => "boolean",
+        }
+    }
+
+    fn accepts(self, value: &Value) -> bool {
+        match self {
+            ArgType::String => value.is_string(),
+            ArgType::Integer => value.is_i64() || value.is_u64(),
+            ArgType::Number => value.is_number(),
+            ArgType::Boolean => value.is_boolean(),
+        }
+    }
+}
+
+impl CustomTool {
+    /// Checks that the name is free and every `{placeholder}` in the command
+    /// is a declared arg.
+    pub fn validate(&self) -> Result<(), String> {
+        if ToolCall::examples().iter().any(|tool| tool.name() == self.name) {
+            return Err(format!("tools.custom {}: a built-in tool has this name", self.name));
+        }
+        for placeholder in placeholders(&self.command) {
+            if !self.args.contains_key(placeholder) {
+                return Err(format!("tools.custom {}: {{{}}} in the command is not a declared arg", self.name, placeholder));
+            }
+        }
+        Ok(())
+    }
+
+    /// The command for a call, after checking `args` against the declared ones.
+    pub fn command(&self, args: &Value) -> Result<String, String> {
+        let empty = Map::new();
+        let args = match args {
+            Value::Object(args) => args,
+            Value::Null => &empty,
+            _ => return Err(format!("{} takes an object of args", self.name)),
+        };
+        if let Some(unknown) = args.keys().find(|key| !self.args.contains_key(*key)) {
+            return Err(format!("{} has no arg {}. Its args: {}", self.name, unknown, self.arg_names()));
+        }
+
+        let mut values = BTreeMap::new();
+        for (name, spec) in &self.args {
+            let value = match args.get(name) {
+                None | Some(Value::Null) if spec.required => return Err(format!("{} needs the {} arg", self.name, name)),
+                None | Some(Value::Null) => String::new(),
+                Some(value) if !spec.kind.accepts(value) => {
+                    return Err(format!("{} of {} must be of type {}, got {}", name, self.name, spec.kind.name(), value));
+                }
+                Some(Value::String(text)) => shell_quote(text),
+                Some(value) => value.to_string(),
+            };
+            values.insert(name.as_str(), value);
+        }
+
+        // One pass over the template, so a `{name}` inside a value is never expanded
+        let mut command = String::new();
+        let mut rest = self.command.as_str();
+        while let Some(open) = rest.find('{') {
+            command.push_str(&rest[..open]);
+            let value = rest[open..]
+                .find('}')
+                .filter(|_| !rest[..open].ends_with('$'))
+                .and_then(|close| Some((close, values.get(&rest[open + 1..open + close])?)));
+            match value {
+                Some((close, value)) => {
+                    command.push_str(value);
+                    rest = &rest[open + close + 1..];
+                }
+                // Shell braces like `${HOME}` or `{a,b}` stay as written
+                None => {
+                    command.push('{');
+                    rest = &rest[open + 1..];
+                }
+            }
+        }
+        command.push_str(rest);
+        Ok(command)
+    }
+
+    fn arg_names(&self) -> String {
+        if self.args.is_empty() {
+            return "none".to_string();
+        }
+        self.args.keys().cloned().collect::<Vec<_>>().join(", ")
+    }
+
+    /// How the tool is shown to the model in the system prompt.
+    pub fn prompt_entry(&self) -> String {
+        let example: Map<String, Value> = self
+            .args
+            .iter()
+            .map(|(name, spec)| {
+                let optional = if spec.required { "" } else { ", optional" };
+                (name.clone(), Value::String(format!("<{}{}>", spec.kind.name(), optional)))
+            })
+            .collect();
+        let mut entry = format!(
+            "{{ \"tool\": \"{}\", \"args\": {} }}\n  {}",
+            self.name,
+            Value::Object(example),
+            self.description
+        );
+        for (name, spec) in &self.args {
+            if let Some(description) = &spec.description {
+                entry.push_str(&format!("\n  - {}: {}", name, description));
+            }
+        }
+        entry
+    }
+}
+
+/// Names inside `{...}` in a command template; shell `${...}` is not one.
+fn placeholders(template: &str) -> Vec<&str> {
+    let mut parts = template.split('{');
+    let mut before = parts.next().unwrap_or_default();
+    parts
+        .filter_map(|rest| {
+            let is_shell = before.ends_with('$');
+            before = rest;
+            rest.split_once('}').map(|(name, _)| name).filter(|_| !is_shell)
+        })
+        .filter(|name| !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_'))
+        .collect()
+}
+
+/// Quotes a value so the shell passes it as a single word.
+fn shell_quote(text: &str) -> String {
+    if cfg!(target_os = "windows") {
+        format!("\"{}\"", text.replace('"', "\\\""))
+    } else {
+        format!("'{}'", text.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tool(toml: &str) -> CustomTool {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn grep() -> CustomTool {
+        tool(
+            r#"
+name = "grep"
+description = "Search"
+command = "grep -rn {pattern} {path} ${HOME}"
+args.pattern = { type = "string" }
+args.path = { type = "string", required = false }
+"#,
+        )
+    }
+
+    #[test]
+    fn test_values_are_quoted() {
+        let command = grep().command(&json!({ "pattern": "it's; rm -rf /" })).unwrap();
+        assert_eq!(command, "grep -rn 'it'\\''s; rm -rf /'  ${HOME}");
+    }
+
+    #[test]
+    fn test_placeholders_in_values_are_not_expanded() {
+        let command = grep().command(&json!({ "pattern": "{path}", "path": "$(reboot)" })).unwrap();
+        assert_eq!(command, "grep -rn '{path}' '$(reboot)' ${HOME}");
+    }
+
+    #[test]
+    fn test_args_are_checked() {
+        assert!(grep().command(&json!({})).unwrap_err().contains("needs the pattern arg"));
+        assert!(grep().command(&json!({ "pattern": 1 })).unwrap_err().contains("must be of type string"));
+        assert!(grep().command(&json!({ "pattern": "x", "flags": "-i" })).unwrap_err().contains("has no arg flags"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(grep().validate().is_ok());
+        let undeclared = tool("name = \"t\"\ndescription = \"\"\ncommand = \"make {target}\"");
+        assert!(undeclared.validate().unwrap_err().contains("{target}"));
+        let built_in = tool("name = \"run_shell\"\ndescription = \"\"\ncommand = \"sh\"");
+        assert!(built_in.validate().unwrap_err().contains("built-in"));
+    }
+}
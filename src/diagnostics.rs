@@ -0,0 +1,347 @@
+use crate::process;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use tokio::process::Command;
+
+/// Maximum number of diagnostics rendered back to the model.
+const MAX_REPORTED: usize = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub level: String,
+    pub code: Option<String>,
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+struct CargoLine {
+    reason: String,
+    message: Option<CargoMessage>,
+}
+
+#[derive(Deserialize)]
+struct CargoMessage {
+    message: String,
+    level: String,
+    code: Option<CargoCode>,
+    spans: Vec<CargoSpan>,
+}
+
+#[derive(Deserialize)]
+struct CargoCode {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct CargoSpan {
+    file_name: String,
+    line_start: usize,
+    column_start: usize,
+    is_primary: bool,
+}
+
+/// Runs the compilers that apply to `root` and returns a compact report.
+/// The boolean is false when any error was found or a compiler could not run.
+pub async fn check_project(root: &Path) -> (String, bool) {
+    let mut diagnostics = Vec::new();
+    let mut failures = Vec::new();
+    let mut ran = Vec::new();
+
+    if root.join("Cargo.toml").exists() {
+        ran.push("cargo check");
+        match cargo_check(root).await {
+            Ok(found) => diagnostics.extend(found),
+            Err(e) => failures.push(e),
+        }
+    }
+    if root.join("rescript.json").exists() || root.join("bsconfig.json").exists() {
+        ran.push("rescript");
+        match rescript_build(root).await {
+            Ok(found) => diagnostics.extend(found),
+            Err(e) => failures.push(e),
+        }
+    }
+
+    if ran.is_empty() {
+        return (format!("No Cargo.toml, rescript.json or bsconfig.json found in {}", root.display()), false);
+    }
+
+    let mut report = render(root, &diagnostics);
+    for failure in &failures {
+        report.push_str(&format!("\n{}\n", failure));
+    }
+    let has_errors = diagnostics.iter().any(|d| d.level == "error");
+    (format!("Ran {}.\n{}", ran.join(" + "), report), !has_errors && failures.is_empty())
+}
+
+async fn cargo_check(root: &Path) -> Result<Vec<Diagnostic>, String> {
+    let mut command = Command::new("cargo");
+    command.args(["check", "--message-format=json", "--all-targets"]).current_dir(root);
+    let out = process::output(command).await.map_err(|e| format!("Failed to run cargo check: {}", e))?;
+
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let diagnostics = parse_cargo_messages(&stdout);
+    if !out.status.success() && diagnostics.is_empty() {
+        // Cargo failed before the compiler ran (bad manifest, missing dependency, ...)
+        return Err(tail(&String::from_utf8_lossy(&out.stderr), 20));
+    }
+    Ok(diagnostics)
+}
+
+fn parse_cargo_messages(stdout: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for line in stdout.lines() {
+        let Ok(parsed) = serde_json::from_str::<CargoLine>(line) else {
+            continue;
+        };
+        if parsed.reason != "compiler-message" {
+            continue;
+        }
+        let Some(message) = parsed.message else {
+            continue;
+        };
+        let Some(span) = message.spans.iter().find(|s| s.is_primary) else {
+            continue;
+        };
+        let diagnostic = Diagnostic {
+            file: span.file_name.clone(),
+            line: span.line_start,
+            column: span.column_start,
+            level: message.level,
+            code: message.code.map(|c| c.code),
+            message: message.message,
+        };
+        // The same warning is reported once per target (bin, test, ...)
+        if !diagnostics.contains(&diagnostic) {
+            diagnostics.push(diagnostic);
+        }
+    }
+    diagnostics
+}
+
+async fn rescript_build(root: &Path) -> Result<Vec<Diagnostic>, String> {
+    let mut command = Command::new("npx");
+    command.args(["rescript", "build"]).current_dir(root);
+    let out = process::output(command).await.map_err(|e| format!("Failed to run rescript: {}", e))?;
+
+    let combined = format!(
+        "{}{}",
+        String::from_utf8_lossy(&out.stdout),
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let diagnostics = parse_rescript_output(&combined, root);
+    if !out.status.success() && diagnostics.is_empty() {
+        return Err(tail(&combined, 20));
+    }
+    Ok(diagnostics)
+}
+
+/// Parses the human-readable ReScript compiler output:
+///
+/// ```text
+///   We've found a bug for you!
+///   /abs/path/src/Main.res:12:3-10
+///
+///   11 │ let a = 1
+///   12 │ let b = c
+///
+///   The value c can't be found
+/// ```
+fn parse_rescript_output(output: &str, root: &Path) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    let mut lines = output.lines().map(str::trim).peekable();
+
+    while let Some(line) = lines.next() {
+        let (level, code) = if line.starts_with("We've found a bug for you!") || line.starts_with("Syntax error!") {
+            ("error", None)
+        } else if let Some(number) = line.strip_prefix("Warning number ") {
+            ("warning", Some(number.trim().to_string()))
+        } else {
+            continue;
+        };
+
+        let Some(location) = lines.next() else { break };
+        let Some((file, line_no, column)) = parse_rescript_location(location, root) else {
+            continue;
+        };
+
+        // Skip the code frame, then collect the message until the next header
+        // or the build's own `FAILED:` lines
+        let mut message = Vec::new();
+        while let Some(next) = lines.peek() {
+            if next.starts_with("We've found a bug for you!")
+                || next.starts_with("Syntax error!")
+                || next.starts_with("Warning number ")
+                || next.starts_with("FAILED:")
+            {
+                break;
+            }
+            let next = lines.next().unwrap_or_default();
+            if next.is_empty() || next.contains('│') {
+                continue;
+            }
+            message.push(next);
+        }
+
+        diagnostics.push(Diagnostic {
+            file,
+            line: line_no,
+            column,
+            level: level.to_string(),
+            code,
+            message: message.join(" "),
+        });
+    }
+    diagnostics
+}
+
+fn parse_rescript_location(location: &str, root: &Path) -> Option<(String, usize, usize)> {
+    // "/abs/src/Main.res:12:3-10" or, for multi-line ranges, "/abs/src/Main.res:12:3-14:2"
+    let split = [".resi:", ".res:"]
+        .iter()
+        .find_map(|ext| location.find(ext).map(|i| i + ext.len() - 1))?;
+    let (file, range) = location.split_at(split);
+    let mut parts = range[1..].split(':');
+    let line = parts.next()?.parse().ok()?;
+    let column = parts.next()?.split('-').next()?.parse().ok()?;
+
+    let file = Path::new(file)
+        .strip_prefix(root.canonicalize().unwrap_or_else(|_| root.to_path_buf()))
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| file.to_string());
+    Some((file, line, column))
+}
+
+/// Groups diagnostics by file as `line:col level[code]: message` plus the
+/// offending source line. Errors come first, so leading warnings cannot push
+/// them past `MAX_REPORTED`.
+fn render(root: &Path, diagnostics: &[Diagnostic]) -> String {
+    let errors = diagnostics.iter().filter(|d| d.level == "error").count();
+    let warnings = diagnostics.iter().filter(|d| d.level == "warning").count();
+    if diagnostics.is_empty() {
+        return "No errors or warnings.\n".to_string();
+    }
+
+    let mut reported: Vec<&Diagnostic> = diagnostics.iter().collect();
+    reported.sort_by_key(|d| d.level != "error");
+    let mut by_file: BTreeMap<&str, Vec<&Diagnostic>> = BTreeMap::new();
+    for d in reported.into_iter().take(MAX_REPORTED) {
+        by_file.entry(d.file.as_str()).or_default().push(d);
+    }
+
+    let mut out = format!("{} errors, {} warnings\n", errors, warnings);
+    for (file, items) in by_file {
+        out.push_str(&format!("\n{}\n", file));
+        let source = fs::read_to_string(root.join(file)).ok();
+        for d in items {
+            let code = d.code.as_ref().map(|c| format!("[{}]", c)).unwrap_or_default();
+            out.push_str(&format!("  {}:{} {}{}: {}\n", d.line, d.column, d.level, code, d.message));
+            if let Some(snippet) = source.as_ref().and_then(|s| s.lines().nth(d.line.saturating_sub(1))) {
+                out.push_str(&format!("    {:>4} | {}\n", d.line, snippet.trim_end()));
+            }
+        }
+    }
+    if diagnostics.len() > MAX_REPORTED {
+        out.push_str(&format!("\n... {} more not shown\n", diagnostics.len() - MAX_REPORTED));
+    }
+    out
+}
+
+/// The last `lines` lines, where build and test failures are summed up.
+pub fn tail(text: &str, lines: usize) -> String {
+    let all: Vec<&str> = text.lines().collect();
+    let start = all.len().saturating_sub(lines);
+    let out = all[start..].join("\n");
+    if start > 0 { format!("... ({} lines cut)\n{}", start, out) } else { out }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(level: &str, line: usize) -> Diagnostic {
+        Diagnostic {
+            file: "src/lib.rs".to_string(),
+            line,
+            column: 1,
+            level: level.to_string(),
+            code: None,
+            message: format!("{} {}", level, line),
+        }
+    }
+
+    #[test]
+    fn test_parse_cargo_messages() {
+        let diagnostics = parse_cargo_messages(include_str!("testdata/cargo_check.jsonl"));
+        // The warning is reported for the bin and the test target; notes without a span are dropped
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic {
+                    file: "src/main.rs".to_string(),
+                    line: 2,
+                    column: 9,
+                    level: "warning".to_string(),
+                    code: Some("unused_variables".to_string()),
+                    message: "unused variable: `unused`".to_string(),
+                },
+                Diagnostic {
+                    file: "src/main.rs".to_string(),
+                    line: 2,
+                    column: 18,
+                    level: "error".to_string(),
+                    code: Some("E0308".to_string()),
+                    message: "mismatched types".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rescript_output() {
+        let diagnostics = parse_rescript_output(include_str!("testdata/rescript_build.txt"), Path::new("/work/app"));
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic {
+                    file: "src/Util.res".to_string(),
+                    line: 3,
+                    column: 7,
+                    level: "warning".to_string(),
+                    code: Some("26".to_string()),
+                    message: "unused variable tmp.".to_string(),
+                },
+                Diagnostic {
+                    file: "src/Main.res".to_string(),
+                    line: 12,
+                    column: 9,
+                    level: "error".to_string(),
+                    code: None,
+                    message: "The value c can't be found".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_reports_errors_before_warnings() {
+        let mut diagnostics: Vec<Diagnostic> = (1..=MAX_REPORTED).map(|line| diagnostic("warning", line)).collect();
+        diagnostics.push(diagnostic("error", 99));
+        let report = render(Path::new("/nonexistent"), &diagnostics);
+        assert!(report.starts_with("1 errors, 20 warnings\n"));
+        assert!(report.contains("99:1 error: error 99"));
+        assert!(!report.contains("warning 20\n"));
+        assert!(report.ends_with("... 1 more not shown\n"));
+    }
+
+    #[test]
+    fn test_tail_marks_cut_lines() {
+        assert_eq!(tail("a\nb\nc", 2), "... (1 lines cut)\nb\nc");
+        assert_eq!(tail("a\nb", 5), "a\nb");
+    }
+}
@@ -0,0 +1,346 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Pages fetched by `fetch_url`, kept as markdown so `docs_lookup` finds them offline.
+pub const WEB_CACHE_DIR: &str = ".rumi/cache/web";
+/// Most text returned by one call; the cached page keeps everything.
+const MAX_OUTPUT_CHARS: usize = 12_000;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// A heading and the lines under it, up to the next heading.
+struct Section {
+    file: PathBuf,
+    line: usize,
+    /// `Title > Heading`, so a hit on `Async` still says which reference it is from.
+    heading: String,
+    body: String,
+}
+
+/// Searches the markdown files in `dirs` (relative to `root`) and the web
+/// cache, and returns the best `limit` sections for `query`. Words found in a
+/// heading count more than words in the text.
+pub fn lookup(root: &Path, dirs: &[String], query: &str, limit: usize) -> (String, bool) {
+    let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).filter(|t| t.len() > 1).collect();
+    if terms.is_empty() {
+        return ("docs_lookup needs a query with at least one word.".to_string(), false);
+    }
+
+    let mut files = Vec::new();
+    for dir in dirs.iter().map(|d| root.join(d)).chain([PathBuf::from(WEB_CACHE_DIR)]) {
+        collect_markdown(&dir, &mut files);
+    }
+    if files.is_empty() {
+        return (format!("No docs found in {}.", dirs.join(", ")), false);
+    }
+
+    let mut scored: Vec<(usize, Section)> = files
+        .iter()
+        .flat_map(|file| sections(file))
+        .filter_map(|section| {
+            let (heading, body) = (section.heading.to_lowercase(), section.body.to_lowercase());
+            let score: usize = terms.iter().map(|t| heading.matches(t.as_str()).count() * 5 + body.matches(t.as_str()).count()).sum();
+            (score > 0).then_some((score, section))
+        })
+        .collect();
+    if scored.is_empty() {
+        return (format!("Nothing in the docs matches \"{}\".", query), true);
+    }
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+    let mut out = String::new();
+    for (_, section) in scored.iter().take(limit.max(1)) {
+        let path = section.file.strip_prefix(root).unwrap_or(&section.file);
+        out.push_str(&format!("## {} ({}:{})\n{}\n\n", section.heading, path.display(), section.line, section.body.trim()));
+    }
+    if scored.len() > limit {
+        out.push_str(&format!("({} more matching sections; narrow the query or raise the limit)", scored.len() - limit));
+    }
+    (truncate(out.trim_end()), true)
+}
+
+fn collect_markdown(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    let mut entries: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            collect_markdown(&path, files);
+        } else if path.extension().is_some_and(|ext| ext == "md") {
+            files.push(path);
+        }
+    }
+}
+
+fn sections(file: &Path) -> Vec<Section> {
+    let Ok(content) = fs::read_to_string(file) else { return Vec::new() };
+    let mut title = None;
+    let mut sections: Vec<Section> = Vec::new();
+    let mut in_fence = false;
+    for (i, line) in content.lines().enumerate() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        let Some(heading) = heading(line).filter(|_| !in_fence) else {
+            match sections.last_mut() {
+                Some(section) => section.body.push_str(&format!("{}\n", line)),
+                None => sections.push(Section {
+                    file: file.to_path_buf(),
+                    line: i + 1,
+                    heading: file.display().to_string(),
+                    body: format!("{}\n", line),
+                }),
+            }
+            continue;
+        };
+        let heading = match &title {
+            None => {
+                title = Some(heading.to_string());
+                heading.to_string()
+            }
+            Some(title) => format!("{} > {}", title, heading),
+        };
+        sections.push(Section { file: file.to_path_buf(), line: i + 1, heading, body: String::new() });
+    }
+    sections
+}
+
+/// The text of a markdown heading line (`## Title`).
+fn heading(line: &str) -> Option<&str> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let text = line[level..].strip_prefix(' ')?;
+    (1..=6).contains(&level).then(|| text.trim())
+}
+
+/// Returns the page at `url` as text, from the cache unless `refresh` is set.
+/// A failed fetch falls back to the cached copy, so a page read once stays
+/// available offline.
+pub async fn fetch_url(url: &str, refresh: bool) -> (String, bool) {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return (format!("Not an http(s) URL: {}", url), false);
+    }
+    let cached = cache_path(url);
+    if !refresh && let Ok(page) = fs::read_to_string(&cached) {
+        return (format!("(cached copy, pass \"refresh\": true to fetch again)\n{}", truncate(&page)), true);
+    }
+
+    let page = match download(url).await {
+        Ok(page) => page,
+        Err(e) => {
+            return match fs::read_to_string(&cached) {
+                Ok(page) => (format!("(fetch failed: {}; showing the cached copy)\n{}", e, truncate(&page)), true),
+                Err(_) => (format!("Failed to fetch {}: {}", url, e), false),
+            };
+        }
+    };
+    if let Err(e) = fs::create_dir_all(WEB_CACHE_DIR).and_then(|()| fs::write(&cached, &page)) {
+        return (format!("{}\n\n(could not cache the page: {})", truncate(&page), e), true);
+    }
+    (truncate(&page), true)
+}
+
+async fn download(url: &str) -> Result<String, String> {
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .user_agent(concat!("rumi/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client.get(url).send().await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("HTTP {}", status));
+    }
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("text/plain")
+        .to_lowercase();
+    let textual = ["text/", "json", "xml", "javascript", "markdown"].iter().any(|kind| content_type.contains(kind));
+    if !textual {
+        return Err(format!("not a text page ({})", content_type));
+    }
+    let body = response.text().await.map_err(|e| e.to_string())?;
+
+    let (title, text) = if content_type.contains("html") { html_to_text(&body) } else { (None, body) };
+    Ok(format!("# {}\nSource: {}\n\n{}\n", title.unwrap_or_else(|| url.to_string()), url, text.trim()))
+}
+
+/// `.rumi/cache/web/<url with every other character replaced by _>-<hash>.md`.
+/// The readable part is cut and lossy, so the hash of the whole URL keeps
+/// pages apart.
+fn cache_path(url: &str) -> PathBuf {
+    let name: String = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .take(150)
+        .collect();
+    Path::new(WEB_CACHE_DIR).join(format!("{}-{:016x}.md", name.trim_end_matches('_'), fnv1a(url)))
+}
+
+/// 64-bit FNV-1a; unlike `DefaultHasher` it stays the same across builds, so
+/// cached pages are found again.
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3))
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_OUTPUT_CHARS) {
+        Some((cut, _)) => format!("{}\n... (cut at {} characters)", &text[..cut], MAX_OUTPUT_CHARS),
+        None => text.to_string(),
+    }
+}
+
+/// Reduces HTML to readable text: scripts and styles are dropped, headings
+/// become markdown headings and list items dashes. Returns the `<title>` too.
+fn html_to_text(html: &str) -> (Option<String>, String) {
+    let lower = html.to_ascii_lowercase();
+    let title = lower.find("<title").and_then(|start| {
+        let open = start + lower[start..].find('>')? + 1;
+        let close = open + lower[open..].find("</title")?;
+        Some(decode_entities(html[open..close].trim())).filter(|t| !t.is_empty())
+    });
+
+    let mut text = String::new();
+    let mut pos = 0;
+    while let Some(offset) = lower[pos..].find('<') {
+        text.push_str(&decode_entities(&html[pos..pos + offset]));
+        let start = pos + offset;
+        let Some(end) = lower[start..].find('>').map(|e| start + e + 1) else { break };
+        let tag = &lower[start + 1..end - 1];
+        let name: String = tag.trim_start_matches('/').chars().take_while(|c| c.is_ascii_alphanumeric()).collect();
+        pos = end;
+        match name.as_str() {
+            // Skip everything up to the closing tag
+            "script" | "style" | "noscript" | "svg" | "head" if !tag.starts_with('/') => {
+                pos = lower[end..].find(&format!("</{}", name)).map_or(html.len(), |close| end + close);
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" if !tag.starts_with('/') => {
+                let level = name[1..].parse().unwrap_or(1);
+                text.push_str(&format!("\n\n{} ", "#".repeat(level + 1)));
+            }
+            "li" if !tag.starts_with('/') => text.push_str("\n- "),
+            "br" | "p" | "div" | "tr" | "pre" | "section" | "article" | "ul" | "ol" | "table" | "h1" | "h2" | "h3" | "h4" | "h5"
+            | "h6" | "blockquote" => text.push('\n'),
+            "td" | "th" => text.push(' '),
+            _ => {}
+        }
+    }
+    text.push_str(&decode_entities(&html[pos.min(html.len())..]));
+
+    // Collapse whitespace, keeping at most one blank line
+    let mut out = String::new();
+    let mut blank = false;
+    for line in text.lines().map(|l| l.split_whitespace().collect::<Vec<_>>().join(" ")) {
+        if line.is_empty() {
+            blank = !out.is_empty();
+            continue;
+        }
+        if blank {
+            out.push('\n');
+            blank = false;
+        }
+        out.push_str(&line);
+        out.push('\n');
+    }
+    (title, out)
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';').filter(|s| *s <= 10) else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_sections_and_lookup() {
+        let root = env::temp_dir().join(format!("rumi-docs-{}", std::process::id()));
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("docs/guide.md"), include_str!("testdata/docs_guide.md")).unwrap();
+
+        let found = sections(&root.join("docs/guide.md"));
+        let headings: Vec<(&str, usize)> = found.iter().map(|s| (s.heading.as_str(), s.line)).collect();
+        // The `# Not a heading` inside the code fence stays in the body
+        assert_eq!(headings, [("Guide", 1), ("Guide > Async", 4), ("Guide > Errors", 13)]);
+        assert!(found[1].body.contains("# Not a heading"));
+
+        let dirs = ["docs".to_string()];
+        let (out, ok) = lookup(&root, &dirs, "async", 1);
+        assert!(ok);
+        assert!(out.starts_with("## Guide > Async (docs/guide.md:4)\n"), "{}", out);
+        assert!(out.ends_with("(1 more matching sections; narrow the query or raise the limit)"), "{}", out);
+        assert_eq!(lookup(&root, &dirs, "nowhere", 3), ("Nothing in the docs matches \"nowhere\".".to_string(), true));
+        assert!(!lookup(&root, &dirs, "a", 3).1);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_html_to_text() {
+        let html = "<html><head><title>Tom &amp; Jerry</title><style>p{}</style></head><body>\
+            <h2>Intro</h2><p>Fish&nbsp;&lt;chips&gt;</p><script>alert(1)</script><ul><li>one</li><li>two</li></ul></body></html>";
+        let (title, text) = html_to_text(html);
+        assert_eq!(title.as_deref(), Some("Tom & Jerry"));
+        assert_eq!(text, "### Intro\n\nFish <chips>\n\n- one\n- two\n");
+    }
+
+    #[test]
+    fn test_decode_entities() {
+        assert_eq!(decode_entities("a &quot;b&quot; &#39;c&#x27; &euro; & d"), "a \"b\" 'c' &euro; & d");
+        assert_eq!(decode_entities("&#x1F600;&#0x;"), "😀&#0x;");
+    }
+
+    #[test]
+    fn test_cache_paths_are_unique() {
+        let path = cache_path("https://a.com/x?y=1");
+        assert!(path.starts_with(WEB_CACHE_DIR));
+        assert!(path.file_name().unwrap().to_string_lossy().starts_with("a.com_x_y_1-"));
+        assert_ne!(path, cache_path("https://a.com/x/y/1"));
+        let long = format!("https://a.com/{}", "x".repeat(200));
+        assert_ne!(cache_path(&format!("{}/1", long)), cache_path(&format!("{}/2", long)));
+        assert_eq!(path, cache_path("https://a.com/x?y=1"));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::config::{Config, Source};
+use crate::llm::{LlmClient, ServerInfo};
+use crate::models::{Models, DEFAULT_PROFILE};
+
+/// The outcome of one check; problems say how to fix them.
+pub enum Finding {
+    Ok(String),
+    Warn { text: String, fix: String },
+    Fail { text: String, fix: String },
+}
+
+/// What `probe` learned about a profile's server.
+pub struct Probe {
+    pub findings: Vec<Finding>,
+    /// The server's `max_model_len` for the profile's model.
+    pub max_model_len: Option<u32>,
+    pub latency_ms: Option<u128>,
+}
+
+impl Probe {
+    pub fn failed(&self) -> bool {
+        self.findings.iter().any(|f| matches!(f, Finding::Fail { .. }))
+    }
+}
+
+/// Asks the profile's server for `/models`: is it up, is the model served,
+/// and does the configured context fit the model's `max_model_len`.
+pub async fn probe(client: &LlmClient, config: &Config) -> Probe {
+    assess(client, config, client.server_info().await)
+}
+
+/// The findings for what the server answered to `/models`.
+fn assess(client: &LlmClient, config: &Config, info: Result<ServerInfo, String>) -> Probe {
+    let mut probe = Probe { findings: Vec::new(), max_model_len: None, latency_ms: None };
+    let default = client.profile == DEFAULT_PROFILE;
+    let info = match info {
+        Ok(info) => info,
+        Err(e) => {
+            let fix = if default {
+                "Start the server (./start_vllm.sh) or point backend.api_url at it (VLLM_API_URL, --api-url).".to_string()
+            } else {
+                format!("Start the server or fix api_url of the {} profile in [[backend.profiles]].", client.profile)
+            };
+            probe.findings.push(Finding::Fail { text: format!("Cannot reach {}: {}", client.api_url(), e), fix });
+            return probe;
+        }
+    };
+    probe.latency_ms = Some(info.latency.as_millis());
+    probe.findings.push(Finding::Ok(format!("Server at {} answered in {} ms", client.api_url(), info.latency.as_millis())));
+
+    let Some(served) = info.served(client.model()) else {
+        let names: Vec<&str> = info.models.iter().map(|m| m.id.as_str()).collect();
+        let setting = if default {
+            "backend.model (MODEL_NAME, --model)".to_string()
+        } else {
+            format!("model of the {} profile", client.profile)
+        };
+        let fix = match names.as_slice() {
+            [] => "The server lists no models; check how it was started.".to_string(),
+            names => format!("Set {} to one of: {}.", setting, names.join(", ")),
+        };
+        probe.findings.push(Finding::Fail { text: format!("The server does not serve {:?}", client.model()), fix });
+        return probe;
+    };
+    probe.max_model_len = served.max_model_len;
+
+    match served.max_model_len {
+        None => probe.findings.push(Finding::Ok(format!("Model {} is served (context length not reported)", served.id))),
+        Some(max) if context_configured(client, config) && client.context_tokens > max => {
+            let setting = if default { "limits.context_tokens (RUMI_CONTEXT_TOKENS)".to_string() } else { format!("context_tokens of the {} profile", client.profile) };
+            probe.findings.push(Finding::Warn {
+                text: format!("Model {} is served with {} tokens of context, but {} tokens are configured", served.id, max, client.context_tokens),
+                fix: format!("Lower {} to {} or less; until then {} is used.", setting, max, max),
+            });
+        }
+        Some(max) => probe.findings.push(Finding::Ok(format!("Model {} is served with {} tokens of context", served.id, max))),
+    }
+    probe
+}
+
+/// Whether the context length was set by the user rather than defaulted, in
+/// which case the server's `max_model_len` only caps it.
+fn context_configured(client: &LlmClient, config: &Config) -> bool {
+    let profile = config.model_profiles.iter().find(|p| p.value.name == client.profile);
+    profile.is_some_and(|p| p.value.context_tokens.is_some()) || !matches!(config.context_tokens.source, Source::Default)
+}
+
+/// The context a client should budget for, given what its server reported.
+pub fn effective_context(client: &LlmClient, config: &Config, max_model_len: Option<u32>) -> u32 {
+    match max_model_len {
+        Some(max) if context_configured(client, config) => client.context_tokens.min(max),
+        Some(max) => max,
+        None => client.context_tokens,
+    }
+}
+
+/// `rumi doctor`: probes every profile and times a minimal completion.
+/// Returns whether everything needed for a run works.
+pub async fn run(config: &Config) -> bool {
+    let models = Models::new(config);
+    let mut healthy = true;
+    for client in models.clients() {
+        println!("Profile {}: {} at {}", client.profile, client.model(), client.api_url());
+        let mut probe = probe(client, config).await;
+        if !probe.failed() {
+            match client.ping().await {
+                Ok(elapsed) => probe.findings.push(Finding::Ok(format!("A minimal completion took {} ms", elapsed.as_millis()))),
+                Err(e) => probe.findings.push(Finding::Fail {
+                    text: format!("A minimal completion failed: {}", e),
+                    fix: "Check the server log; the model may still be loading or out of memory.".to_string(),
+                }),
+            }
+        }
+        healthy &= !probe.failed();
+        for finding in &probe.findings {
+            match finding {
+                Finding::Ok(text) => println!("  ok    {}", text),
+                Finding::Warn { text, fix } => println!("  warn  {}\n        fix: {}", text, fix),
+                Finding::Fail { text, fix } => println!("  FAIL  {}\n        fix: {}", text, fix),
+            }
+        }
+    }
+    for (role, names) in &config.roles {
+        println!("Role {} -> {}", role.name(), names.value.join(" | "));
+    }
+    healthy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Setting;
+    use crate::llm::ServedModel;
+    use crate::models::ModelProfile;
+    use std::time::Duration;
+
+    /// A client of `profile`, whose own `context_tokens` is set when given.
+    fn client(config: &mut Config, profile: Option<(&str, Option<u32>)>) -> LlmClient {
+        match profile {
+            None => LlmClient::new(config),
+            Some((name, context_tokens)) => {
+                let profile = ModelProfile {
+                    name: name.to_string(),
+                    model: "qwen3-14b".to_string(),
+                    api_url: None,
+                    context_tokens,
+                    base_temperature: None,
+                    max_temperature: None,
+                    cost_per_1k_prompt: None,
+                    cost_per_1k_completion: None,
+                };
+                config.model_profiles.push(Setting { value: profile.clone(), source: Source::Default });
+                LlmClient::from_profile(&profile, config)
+            }
+        }
+    }
+
+    fn serving(models: &[(&str, Option<u32>)]) -> Result<ServerInfo, String> {
+        let models = models.iter().map(|(id, max_model_len)| ServedModel { id: id.to_string(), max_model_len: *max_model_len }).collect();
+        Ok(ServerInfo { models, latency: Duration::from_millis(12) })
+    }
+
+    #[test]
+    fn test_effective_context() {
+        let mut config = Config::default();
+        let defaulted = client(&mut config, None);
+        // A defaulted context follows the server, both ways
+        assert_eq!(effective_context(&defaulted, &config, Some(131_072)), 131_072);
+        assert_eq!(effective_context(&defaulted, &config, Some(4096)), 4096);
+        assert_eq!(effective_context(&defaulted, &config, None), config.context_tokens.value);
+
+        // A configured one is only capped
+        let configured = client(&mut config, Some(("big", Some(16_384))));
+        assert_eq!(effective_context(&configured, &config, Some(131_072)), 16_384);
+        assert_eq!(effective_context(&configured, &config, Some(8192)), 8192);
+        assert_eq!(effective_context(&configured, &config, None), 16_384);
+
+        config.context_tokens.source = Source::Env("RUMI_CONTEXT_TOKENS");
+        let from_env = client(&mut config, None);
+        assert_eq!(effective_context(&from_env, &config, Some(131_072)), config.context_tokens.value);
+    }
+
+    #[test]
+    fn test_unserved_model_names_the_setting() {
+        let mut config = Config::default();
+        config.model.value = "qwen3-32b".to_string();
+        let default = client(&mut config, None);
+        let probe = assess(&default, &config, serving(&[("qwen3-14b", None), ("qwen3-8b", None)]));
+        assert!(probe.failed());
+        let Some(Finding::Fail { text, fix }) = probe.findings.last() else { panic!("no failure") };
+        assert_eq!(text, "The server does not serve \"qwen3-32b\"");
+        assert_eq!(fix, "Set backend.model (MODEL_NAME, --model) to one of: qwen3-14b, qwen3-8b.");
+
+        let profile = client(&mut config, Some(("big", None)));
+        let probe = assess(&profile, &config, serving(&[]));
+        let Some(Finding::Fail { fix, .. }) = probe.findings.last() else { panic!("no failure") };
+        assert_eq!(fix, "The server lists no models; check how it was started.");
+        let probe = assess(&profile, &config, serving(&[("other", None)]));
+        let Some(Finding::Fail { fix, .. }) = probe.findings.last() else { panic!("no failure") };
+        assert_eq!(fix, "Set model of the big profile to one of: other.");
+    }
+
+    #[test]
+    fn test_served_model_reports_its_context() {
+        let mut config = Config::default();
+        let profile = client(&mut config, Some(("big", Some(65_536))));
+        let probe = assess(&profile, &config, serving(&[("qwen3-14b", Some(32_768))]));
+        assert!(!probe.failed());
+        assert_eq!((probe.max_model_len, probe.latency_ms), (Some(32_768), Some(12)));
+        assert!(matches!(probe.findings.last(), Some(Finding::Warn { fix, .. }) if fix.starts_with("Lower context_tokens of the big profile to 32768")));
+
+        let probe = assess(&profile, &config, Err("connection refused".to_string()));
+        assert!(probe.failed());
+        assert_eq!(probe.max_model_len, None);
+    }
+}
@@ -0,0 +1,347 @@
+use crate::usage::UsageTotals;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Mutex;
+
+/// Held while a reporter asks on stdin, so parallel sub-agents ask one at a
+/// time instead of interleaving their prompts and taking each other's answers.
+static STDIN: Mutex<()> = Mutex::new(());
+
+/// Ids of `approval_request` events, shared by all reporters so that answers
+/// on the shared stdin cannot be taken by the wrong sub-agent.
+static NEXT_APPROVAL: AtomicU64 = AtomicU64::new(1);
+
+/// Everything the agent loop reports while it works. The console prints these
+/// as text, the TUI renders them into panes and `--output json` streams them.
+#[derive(Debug, Clone)]
+pub enum AgentEvent {
+    /// A request is about to go out; `context_tokens` is the context of the
+    /// profile that was picked for it.
+    Thinking { temperature: f32, attempt: u32, model: String, context_tokens: u32 },
+    Assistant { text: String },
+    ToolCall { name: String, args: Value },
+    /// A `write_file` that is about to be applied, as a unified diff.
+    PendingWrite { path: String, diff: String },
+    /// A tool call is waiting for the user; answered through `Reporter::approve`.
+    ApprovalRequest { tool: String, summary: String },
+    /// The run was interrupted and waits for `Reporter::follow_up`.
+    FollowUpRequest,
+    ToolResult { name: String, output: String, success: bool },
+    Usage { status_line: String, context_tokens: u32, session_tokens: u64 },
+    Info { text: String },
+    Error { text: String },
+    /// The run is over; `summary` is the human-readable usage report, empty
+    /// when it was already shown (the report of a parallel run).
+    Done { success: bool, session: String, summary: String, totals: UsageTotals },
+}
+
+pub trait Reporter: Send + Sync {
+    fn report(&self, event: AgentEvent);
+
+    /// Asks the user whether a tool listed under `[tools] approve` may run.
+    fn approve(&self, tool: &str, summary: &str) -> bool;
+
+    /// Lets the surface apply a `write_file` itself (an editor applying a
+    /// workspace edit). `None` means the agent writes the file as usual.
+    fn apply_write(&self, _path: &str, _content: &str) -> Option<Result<String, String>> {
+        None
+    }
+
+    /// Asks how to go on after Ctrl-C cancelled the current step. `None`
+    /// ends the run.
+    fn follow_up(&self) -> Option<String> {
+        None
+    }
+}
+
+/// The machine-readable form of an event, shared by `--output json` and the
+/// editor bridge. Approval and follow-up requests have no form of their own:
+/// the surface sends them from `approve` and `follow_up`, where it can wait
+/// for the answer.
+pub fn event_json(event: AgentEvent) -> Option<Value> {
+    let value = match event {
+        AgentEvent::Thinking { temperature, attempt, model, context_tokens } => {
+            // Round so f32 noise (0.699999988) does not leak into the output
+            let temperature = (temperature as f64 * 100.0).round() / 100.0;
+            json!({ "type": "thinking", "temperature": temperature, "attempt": attempt, "model": model, "context_tokens": context_tokens })
+        }
+        AgentEvent::Assistant { text } => json!({ "type": "assistant_message", "text": text }),
+        AgentEvent::ToolCall { name, args } => json!({ "type": "tool_call", "tool": name, "args": args }),
+        AgentEvent::PendingWrite { path, diff } => json!({ "type": "pending_write", "path": path, "diff": diff }),
+        AgentEvent::ApprovalRequest { .. } | AgentEvent::FollowUpRequest => return None,
+        AgentEvent::ToolResult { name, output, success } => {
+            json!({ "type": "tool_result", "tool": name, "output": output, "success": success })
+        }
+        AgentEvent::Usage { context_tokens, session_tokens, .. } => {
+            json!({ "type": "usage", "context_tokens": context_tokens, "session_tokens": session_tokens })
+        }
+        AgentEvent::Info { text } => json!({ "type": "info", "text": text.trim() }),
+        AgentEvent::Error { text } => json!({ "type": "error", "text": text }),
+        AgentEvent::Done { success, session, totals, .. } => {
+            json!({ "type": "done", "success": success, "session": session, "usage": totals })
+        }
+    };
+    Some(value)
+}
+
+/// Plain terminal output. Sub-agents tag their banners with their unit id.
+#[derive(Default)]
+pub struct ConsoleReporter {
+    unit: Option<usize>,
+    /// Diff of the write about to happen, shown only when asking for approval.
+    pending_diff: Mutex<Option<String>>,
+}
+
+impl ConsoleReporter {
+    pub fn unit(id: usize) -> Self {
+        ConsoleReporter { unit: Some(id), ..ConsoleReporter::default() }
+    }
+
+    fn banner(&self, title: &str) -> String {
+        match self.unit {
+            Some(id) => format!("--- [unit {}] {} ---", id, title),
+            None => format!("--- {} ---", title),
+        }
+    }
+}
+
+impl Reporter for ConsoleReporter {
+    fn report(&self, event: AgentEvent) {
+        match event {
+            AgentEvent::Thinking { temperature, attempt, model, .. } => {
+                println!("Thinking with {} at Temp: {}, Attempt: {}", model, temperature, attempt)
+            }
+            AgentEvent::Assistant { text } => println!("\n{}\n{}", self.banner("Rumi Thinks"), text),
+            AgentEvent::PendingWrite { diff, .. } => {
+                *self.pending_diff.lock().unwrap_or_else(|e| e.into_inner()) = Some(diff);
+            }
+            AgentEvent::ToolCall { .. } | AgentEvent::ApprovalRequest { .. } | AgentEvent::FollowUpRequest => {}
+            AgentEvent::ToolResult { name, output, success } => {
+                let status = if success { "" } else { ", failed" };
+                let title = format!("Tool Execution ({}{})", name, status);
+                println!("\n{}\n{}", self.banner(&title), output);
+            }
+            AgentEvent::Usage { status_line, .. } => println!("{}", status_line),
+            AgentEvent::Info { text } => println!("{}", text),
+            AgentEvent::Error { text } => eprintln!("Error: {}", text),
+            AgentEvent::Done { session, summary, .. } => {
+                if !summary.is_empty() {
+                    println!("\n{}\nSession saved to {}", summary, session);
+                }
+            }
+        }
+    }
+
+    fn approve(&self, tool: &str, summary: &str) -> bool {
+        let _asking = STDIN.lock().unwrap_or_else(|e| e.into_inner());
+        let diff = self.pending_diff.lock().unwrap_or_else(|e| e.into_inner()).take();
+        println!("\n{}", self.banner("Approval"));
+        if let Some(diff) = diff.filter(|_| tool == "write_file") {
+            print!("{}", diff);
+        }
+        print!("Allow {}? [y/N] ", summary);
+        let _ = io::stdout().flush();
+        let mut answer = String::new();
+        io::stdin().lock().read_line(&mut answer).is_ok() && matches!(answer.trim(), "y" | "Y" | "yes")
+    }
+
+    fn follow_up(&self) -> Option<String> {
+        // Sub-agents share the terminal; they just stop
+        if self.unit.is_some() {
+            return None;
+        }
+        print!("What next? (/compact summarizes the history, Enter stops, Ctrl-C again exits) ");
+        let _ = io::stdout().flush();
+        let mut answer = String::new();
+        io::stdin().lock().read_line(&mut answer).ok()?;
+        Some(answer.trim().to_string()).filter(|answer| !answer.is_empty())
+    }
+}
+
+/// Forwards events to another thread (the TUI), which answers approvals on
+/// `replies` and follow-ups on `instructions`.
+pub struct ChannelReporter {
+    sender: Sender<AgentEvent>,
+    replies: Mutex<Receiver<bool>>,
+    instructions: Mutex<Receiver<String>>,
+}
+
+impl ChannelReporter {
+    pub fn new(sender: Sender<AgentEvent>, replies: Receiver<bool>, instructions: Receiver<String>) -> Self {
+        ChannelReporter { sender, replies: Mutex::new(replies), instructions: Mutex::new(instructions) }
+    }
+}
+
+impl Reporter for ChannelReporter {
+    fn report(&self, event: AgentEvent) {
+        // The UI may already be closed; the agent keeps going regardless
+        let _ = self.sender.send(event);
+    }
+
+    fn approve(&self, tool: &str, summary: &str) -> bool {
+        self.report(AgentEvent::ApprovalRequest { tool: tool.to_string(), summary: summary.to_string() });
+        let replies = self.replies.lock().unwrap_or_else(|e| e.into_inner());
+        replies.recv().unwrap_or(false)
+    }
+
+    fn follow_up(&self) -> Option<String> {
+        self.report(AgentEvent::FollowUpRequest);
+        let instructions = self.instructions.lock().unwrap_or_else(|e| e.into_inner());
+        instructions.recv().ok().filter(|instruction| !instruction.is_empty())
+    }
+}
+
+/// `--output json`: one JSON object per line on stdout, tagged with `type`.
+pub struct JsonReporter {
+    unit: Option<usize>,
+    /// Read approval and follow-up answers from stdin instead of denying them.
+    interactive: bool,
+}
+
+impl JsonReporter {
+    pub fn new(unit: Option<usize>, interactive: bool) -> Self {
+        JsonReporter { unit, interactive }
+    }
+
+    fn emit(&self, mut value: Value) {
+        if let Some(unit) = self.unit {
+            value["unit"] = json!(unit);
+        }
+        // One println per event keeps lines whole when sub-agents share stdout
+        println!("{}", value);
+    }
+
+    /// Reads stdin until an object `is_answer` accepts arrives; `None` on EOF.
+    fn read_answer(&self, is_answer: impl Fn(&Value) -> bool) -> Option<Value> {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            let Ok(line) = line else { break };
+            let Ok(answer) = serde_json::from_str::<Value>(&line) else {
+                self.report(AgentEvent::Error { text: format!("Expected a JSON answer, got: {}", line.trim()) });
+                continue;
+            };
+            if is_answer(&answer) {
+                return Some(answer);
+            }
+        }
+        None
+    }
+}
+
+impl Reporter for JsonReporter {
+    fn report(&self, event: AgentEvent) {
+        if let Some(value) = event_json(event) {
+            self.emit(value);
+        }
+    }
+
+    fn approve(&self, tool: &str, summary: &str) -> bool {
+        let _asking = STDIN.lock().unwrap_or_else(|e| e.into_inner());
+        let id = NEXT_APPROVAL.fetch_add(1, Ordering::Relaxed);
+        self.emit(json!({
+            "type": "approval_request",
+            "id": id,
+            "tool": tool,
+            "summary": summary,
+            "interactive": self.interactive,
+        }));
+        // `{"id": <id>, "approved": bool}`; EOF denies
+        self.interactive
+            && self
+                .read_answer(|answer| answer["id"].as_u64() == Some(id))
+                .and_then(|answer| answer["approved"].as_bool())
+                .unwrap_or(false)
+    }
+
+    /// Waits for `{"type": "follow_up", "text": "..."}`; empty text or EOF stops.
+    fn follow_up(&self) -> Option<String> {
+        // Sub-agents share stdin; they just stop
+        if !self.interactive || self.unit.is_some() {
+            return None;
+        }
+        let _asking = STDIN.lock().unwrap_or_else(|e| e.into_inner());
+        self.emit(json!({ "type": "follow_up_request" }));
+        let answer = self.read_answer(|answer| answer["type"] == "follow_up")?;
+        Some(answer["text"].as_str().unwrap_or_default().trim().to_string()).filter(|text| !text.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> String {
+        text.to_string()
+    }
+
+    #[test]
+    fn test_thinking_json() {
+        let event = AgentEvent::Thinking { temperature: 0.7, attempt: 2, model: text("qwen3"), context_tokens: 32768 };
+        assert_eq!(
+            event_json(event),
+            Some(json!({ "type": "thinking", "temperature": 0.7, "attempt": 2, "model": "qwen3", "context_tokens": 32768 }))
+        );
+    }
+
+    #[test]
+    fn test_assistant_json() {
+        let event = AgentEvent::Assistant { text: text("Reading the file.") };
+        assert_eq!(event_json(event), Some(json!({ "type": "assistant_message", "text": "Reading the file." })));
+    }
+
+    #[test]
+    fn test_tool_call_json() {
+        let event = AgentEvent::ToolCall { name: text("read_file"), args: json!({ "path": "a.rs" }) };
+        assert_eq!(event_json(event), Some(json!({ "type": "tool_call", "tool": "read_file", "args": { "path": "a.rs" } })));
+    }
+
+    #[test]
+    fn test_pending_write_json() {
+        let event = AgentEvent::PendingWrite { path: text("a.rs"), diff: text("+fn a() {}\n") };
+        assert_eq!(event_json(event), Some(json!({ "type": "pending_write", "path": "a.rs", "diff": "+fn a() {}\n" })));
+    }
+
+    #[test]
+    fn test_requests_have_no_json() {
+        // Sent by `approve` and `follow_up`, which wait for the answer
+        assert_eq!(event_json(AgentEvent::ApprovalRequest { tool: text("run_shell"), summary: text("run_shell ls") }), None);
+        assert_eq!(event_json(AgentEvent::FollowUpRequest), None);
+    }
+
+    #[test]
+    fn test_tool_result_json() {
+        let event = AgentEvent::ToolResult { name: text("check"), output: text("1 error"), success: false };
+        assert_eq!(event_json(event), Some(json!({ "type": "tool_result", "tool": "check", "output": "1 error", "success": false })));
+    }
+
+    #[test]
+    fn test_usage_json() {
+        let event = AgentEvent::Usage { status_line: text("ctx 1200"), context_tokens: 1200, session_tokens: 5000 };
+        assert_eq!(event_json(event), Some(json!({ "type": "usage", "context_tokens": 1200, "session_tokens": 5000 })));
+    }
+
+    #[test]
+    fn test_info_json() {
+        let event = AgentEvent::Info { text: text("\nStep 1 done.\n") };
+        assert_eq!(event_json(event), Some(json!({ "type": "info", "text": "Step 1 done." })));
+    }
+
+    #[test]
+    fn test_error_json() {
+        let event = AgentEvent::Error { text: text("connection refused") };
+        assert_eq!(event_json(event), Some(json!({ "type": "error", "text": "connection refused" })));
+    }
+
+    #[test]
+    fn test_done_json() {
+        let totals = UsageTotals { turns: 3, prompt_tokens: 900, completion_tokens: 100, cost: 0.5, ..UsageTotals::default() };
+        let event = AgentEvent::Done { success: true, session: text("s1"), summary: text("3 turns"), totals };
+        let usage = json!({
+            "turns": 3, "prompt_tokens": 900, "completion_tokens": 100,
+            "latency_ms": 0, "tool_ms": 0, "avg_ttft_ms": 0, "cost": 0.5,
+        });
+        assert_eq!(event_json(event), Some(json!({ "type": "done", "success": true, "session": "s1", "usage": usage })));
+    }
+}
@@ -0,0 +1,234 @@
+use serde::{Deserialize, Serialize};
+use crate::process;
+use std::fs;
+use std::path::{self, Path, PathBuf};
+use tokio::process::Command;
+
+/// Longest diff handed to the model before it is cut.
+const MAX_DIFF_LINES: usize = 300;
+
+/// Isolated sessions get a worktree here, named after the session id.
+const WORKTREES_DIR: &str = ".rumi/worktrees";
+
+/// Runs git in `dir` and returns stdout, or stderr as the error.
+async fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let mut command = Command::new("git");
+    command.current_dir(dir).args(args);
+    let out = process::output(command).await.map_err(|e| format!("Failed to run git: {}", e))?;
+    if out.status.success() {
+        Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+    } else {
+        Err(String::from_utf8_lossy(&out.stderr).trim().to_string())
+    }
+}
+
+fn into_result(result: Result<String, String>) -> (String, bool) {
+    match result {
+        Ok(output) => (output, true),
+        Err(e) => (e, false),
+    }
+}
+
+/// Branch plus files grouped by state, from `git status --porcelain`.
+pub async fn status(dir: &Path) -> (String, bool) {
+    into_result(git(dir, &["status", "--porcelain=v1", "--branch"]).await.map(|raw| render_status(&raw)))
+}
+
+fn render_status(raw: &str) -> String {
+    let mut branch = String::new();
+    let (mut staged, mut modified, mut untracked, mut conflicted) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for line in raw.lines() {
+        if let Some(header) = line.strip_prefix("## ") {
+            branch = header.replace("...", " tracking ");
+            continue;
+        }
+        let (code, path) = line.split_at(line.len().min(3));
+        let mut code = code.chars();
+        let (index, worktree) = (code.next().unwrap_or(' '), code.next().unwrap_or(' '));
+        match (index, worktree) {
+            ('?', '?') => untracked.push(path.to_string()),
+            ('U', _) | (_, 'U') | ('A', 'A') | ('D', 'D') => conflicted.push(path.to_string()),
+            _ => {
+                if index != ' ' {
+                    staged.push(format!("{} {}", index, path));
+                }
+                if worktree != ' ' {
+                    modified.push(format!("{} {}", worktree, path));
+                }
+            }
+        }
+    }
+
+    let mut out = format!("Branch: {}\n", branch);
+    for (title, files) in [("Staged", staged), ("Modified", modified), ("Conflicted", conflicted), ("Untracked", untracked)] {
+        if !files.is_empty() {
+            out.push_str(&format!("{} ({}):\n  {}\n", title, files.len(), files.join("\n  ")));
+        }
+    }
+    if raw.lines().count() <= 1 {
+        out.push_str("Working tree clean\n");
+    }
+    out
+}
+
+/// `--stat` summary followed by the unified diff, cut at `MAX_DIFF_LINES`.
+pub async fn diff(dir: &Path, path: Option<&str>, staged: bool) -> (String, bool) {
+    let args = |format: &'static str| {
+        let mut args = vec!["diff", "--no-color", format];
+        if staged {
+            args.push("--cached");
+        }
+        with_path_args(args, path)
+    };
+    let (stat, diff) = match (git(dir, &args("--stat")).await, git(dir, &args("-U3")).await) {
+        (Ok(stat), Ok(diff)) => (stat, diff),
+        (Err(e), _) | (_, Err(e)) => return (e, false),
+    };
+    if diff.is_empty() {
+        let scope = path.map(|p| format!(" in {}", p)).unwrap_or_default();
+        return (format!("No {}changes{}.", if staged { "staged " } else { "" }, scope), true);
+    }
+    let lines: Vec<&str> = diff.lines().collect();
+    let mut out = format!("{}\n{}", stat.trim_end(), lines[..lines.len().min(MAX_DIFF_LINES)].join("\n"));
+    if lines.len() > MAX_DIFF_LINES {
+        out.push_str(&format!("\n... ({} more lines, pass a path to narrow the diff)", lines.len() - MAX_DIFF_LINES));
+    }
+    (out, true)
+}
+
+fn with_path_args<'a>(mut args: Vec<&'a str>, path: Option<&'a str>) -> Vec<&'a str> {
+    if let Some(path) = path {
+        args.extend(["--", path]);
+    }
+    args
+}
+
+/// One line per commit: short hash, date, author, subject.
+pub async fn log(dir: &Path, path: Option<&str>, limit: usize) -> (String, bool) {
+    let limit = limit.to_string();
+    let args = vec!["log", "--format=%h %ad %an: %s", "--date=short", "-n", &limit];
+    into_result(git(dir, &with_path_args(args, path)).await.map(|out| {
+        if out.is_empty() { "No commits.".to_string() } else { out }
+    }))
+}
+
+/// `L<n> <hash> <author> (<commit subject>): <line>` for each line in the range.
+pub async fn blame(dir: &Path, path: &str, start: usize, end: usize) -> (String, bool) {
+    let range = format!("{},{}", start.max(1), end.max(start));
+    into_result(git(dir, &["blame", "--line-porcelain", "-L", &range, "--", path]).await.map(|raw| {
+        let mut out = String::new();
+        let (mut hash, mut line_no, mut author, mut summary) = (String::new(), String::new(), String::new(), String::new());
+        for line in raw.lines() {
+            if let Some(content) = line.strip_prefix('\t') {
+                out.push_str(&format!("L{} {} {} ({}): {}\n", line_no, hash, author, summary, content));
+            } else if let Some(value) = line.strip_prefix("author ") {
+                author = value.to_string();
+            } else if let Some(value) = line.strip_prefix("summary ") {
+                summary = value.to_string();
+            } else if let Some((sha, rest)) = line.split_once(' ')
+                && sha.len() == 40
+                && sha.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                hash = sha[..7].to_string();
+                line_no = rest.split(' ').nth(1).unwrap_or_default().to_string();
+            }
+        }
+        out
+    }))
+}
+
+/// Stages `files` (when given) and commits what is staged.
+pub async fn commit(dir: &Path, message: &str, files: &[String]) -> (String, bool) {
+    into_result(stage_and_commit(dir, message, files).await)
+}
+
+async fn stage_and_commit(dir: &Path, message: &str, files: &[String]) -> Result<String, String> {
+    if !files.is_empty() {
+        let mut args = vec!["add", "--"];
+        args.extend(files.iter().map(String::as_str));
+        git(dir, &args).await?;
+    }
+    // `diff --quiet` succeeds when there is no difference
+    if git(dir, &["diff", "--cached", "--quiet"]).await.is_ok() {
+        return Err("Nothing is staged. Pass the files to commit.".to_string());
+    }
+    git(dir, &["commit", "-m", message]).await?;
+    git(dir, &["log", "-1", "--format=Committed %h: %s"]).await
+}
+
+/// The worktree and branch an isolated session works in.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Worktree {
+    pub branch: String,
+    pub path: PathBuf,
+}
+
+/// Checks out `HEAD` on a new `rumi/<id>` branch in `.rumi/worktrees/<id>`.
+pub async fn create_worktree(id: &str) -> Result<Worktree, String> {
+    let branch = format!("rumi/{}", id);
+    // Otherwise the worktrees show up in `git status` and `git add -A` stages them as embedded repositories
+    let ignore = Path::new(WORKTREES_DIR).join(".gitignore");
+    if !ignore.exists() {
+        fs::create_dir_all(WORKTREES_DIR).and_then(|_| fs::write(&ignore, "*\n")).map_err(|e| e.to_string())?;
+    }
+    let path = path::absolute(Path::new(WORKTREES_DIR).join(id)).map_err(|e| e.to_string())?;
+    git(Path::new("."), &["worktree", "add", "-b", &branch, &path.to_string_lossy(), "HEAD"]).await?;
+    Ok(Worktree { branch, path })
+}
+
+/// Commits whatever the session left uncommitted, merges its branch into the
+/// current one and removes the worktree. On a failed merge both are kept.
+pub async fn merge_worktree(worktree: &Worktree, message: &str) -> Result<String, String> {
+    let here = Path::new(".");
+    if worktree.path.exists() {
+        git(&worktree.path, &["add", "-A"]).await?;
+        if git(&worktree.path, &["diff", "--cached", "--quiet"]).await.is_err() {
+            git(&worktree.path, &["commit", "-m", message]).await?;
+        }
+    }
+
+    let commits = git(here, &["rev-list", "--count", &format!("HEAD..{}", worktree.branch)]).await?;
+    let commits = commits.trim();
+    if commits == "0" {
+        remove_worktree(worktree).await?;
+        return Ok(format!("{} has no changes; removed it.", worktree.branch));
+    }
+    git(here, &["merge", "--no-ff", "--no-edit", &worktree.branch]).await.map_err(|e| {
+        format!("Merging {} failed, the branch and worktree are kept. Resolve it with git, then run `session discard`.\n{}", worktree.branch, e)
+    })?;
+    remove_worktree(worktree).await?;
+    Ok(format!("Merged {} commit(s) from {}.", commits, worktree.branch))
+}
+
+/// Drops the worktree and its branch, with everything on them.
+pub async fn remove_worktree(worktree: &Worktree) -> Result<(), String> {
+    let here = Path::new(".");
+    if worktree.path.exists() {
+        git(here, &["worktree", "remove", "--force", &worktree.path.to_string_lossy()]).await?;
+    }
+    git(here, &["branch", "-D", &worktree.branch]).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_status() {
+        let raw = "## main...origin/main [ahead 1]\nMM a.txt\n M b.txt\nD  \"c d.txt\"\nUU merge.rs\n?? \"c d.txt\"\n?? new.txt\n";
+        assert_eq!(
+            render_status(raw),
+            "Branch: main tracking origin/main [ahead 1]\n\
+             Staged (2):\n  M a.txt\n  D \"c d.txt\"\n\
+             Modified (2):\n  M a.txt\n  M b.txt\n\
+             Conflicted (1):\n  merge.rs\n\
+             Untracked (2):\n  \"c d.txt\"\n  new.txt\n"
+        );
+    }
+
+    #[test]
+    fn test_render_clean_status() {
+        assert_eq!(render_status("## main\n"), "Branch: main\nWorking tree clean\n");
+    }
+}
@@ -0,0 +1,118 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+/// Ctrl-C handling shared by the agents of a run. The first press cancels
+/// whatever is in flight (a model request or a tool) and the agent asks how
+/// to go on; a second press before it resumes exits, once the session is
+/// saved. Cancelling only stops the work: files a shell command or custom
+/// tool already changed stay changed, and only `write_file` is all or nothing.
+#[derive(Clone)]
+pub struct Interrupt {
+    pressed: Arc<watch::Sender<bool>>,
+    exit: Arc<Mutex<Exit>>,
+}
+
+/// Where a second press stands; the agent and the signal listener both look.
+#[derive(Default)]
+struct Exit {
+    /// The agent saved the session after the first press.
+    saved: bool,
+    /// A second press came before it did.
+    requested: bool,
+}
+
+impl Default for Interrupt {
+    /// A handle that only `press` presses, for agents driven by an editor or the TUI.
+    fn default() -> Self {
+        Interrupt { pressed: Arc::new(watch::channel(false).0), exit: Arc::default() }
+    }
+}
+
+impl Interrupt {
+    /// Takes over Ctrl-C for the rest of the process.
+    pub fn listen() -> Self {
+        let interrupt = Interrupt::default();
+        let handle = interrupt.clone();
+        tokio::spawn(async move {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                if handle.press() && handle.request_exit() {
+                    exit_process();
+                }
+            }
+        });
+        interrupt
+    }
+
+    /// Whether a second press may exit right away: the session is saved, or
+    /// the user pressed a third time while the agent was busy elsewhere.
+    /// Otherwise `saved` exits once the agent gets there.
+    fn request_exit(&self) -> bool {
+        let mut exit = self.exit.lock().unwrap_or_else(|e| e.into_inner());
+        let now = exit.saved || exit.requested;
+        exit.requested = true;
+        now
+    }
+
+    /// Called by the agent once it saved the session after a press; exits
+    /// if a second press is waiting for that.
+    pub fn saved(&self) {
+        let mut exit = self.exit.lock().unwrap_or_else(|e| e.into_inner());
+        if exit.requested {
+            exit_process();
+        }
+        exit.saved = true;
+    }
+
+    /// Presses Ctrl-C for a surface that gets it as input (the TUI, an
+    /// editor). Returns whether it was pressed already.
+    pub fn press(&self) -> bool {
+        self.pressed.send_replace(true)
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        *self.pressed.borrow()
+    }
+
+    /// Clears a press once the agent goes back to work.
+    pub fn reset(&self) {
+        *self.exit.lock().unwrap_or_else(|e| e.into_inner()) = Exit::default();
+        self.pressed.send_replace(false);
+    }
+
+    /// Runs `work` unless Ctrl-C comes first, in which case it is dropped
+    /// (killing its child processes) and `None` is returned.
+    pub async fn cancellable<T>(&self, work: impl Future<Output = T>) -> Option<T> {
+        let mut pressed = self.pressed.subscribe();
+        tokio::select! {
+            result = work => Some(result),
+            _ = pressed.wait_for(|pressed| *pressed) => None,
+        }
+    }
+}
+
+fn exit_process() -> ! {
+    eprintln!("\nExiting.");
+    std::process::exit(130);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_waits_for_the_save() {
+        let interrupt = Interrupt::default();
+        assert!(!interrupt.press());
+        assert!(interrupt.press());
+        // Not saved yet: `saved` exits instead, unless the user presses again
+        assert!(!interrupt.request_exit());
+        assert!(interrupt.request_exit());
+
+        interrupt.reset();
+        assert!(!interrupt.is_pressed());
+        interrupt.press();
+        interrupt.saved();
+        assert!(interrupt.request_exit());
+    }
+}
@@ -0,0 +1,442 @@
+use crate::config::Config;
+use crate::models::{ModelProfile, DEFAULT_PROFILE};
+use crate::usage::TurnUsage;
+use base64::Engine;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Largest image sent to the model; bigger ones blow the context of local VL models.
+const MAX_IMAGE_BYTES: usize = 8 * 1024 * 1024;
+/// How long `/models` may take before the server counts as down.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A client for one model profile (see `models`).
+#[derive(Clone)]
+pub struct LlmClient {
+    client: Client,
+    pub profile: String,
+    api_url: String,
+    model_name: String,
+    base_temp: f32,
+    max_temp: f32,
+    max_tokens: u32,
+    pub context_tokens: u32,
+    /// `(prompt, completion)` cost per 1k tokens.
+    prices: (f64, f64),
+    /// Requests in flight, shared by the clones of this client.
+    busy: Arc<AtomicUsize>,
+}
+
+/// Counts a request as in flight until dropped, also when it is cancelled.
+pub struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    fn start(busy: &Arc<AtomicUsize>) -> Self {
+        busy.fetch_add(1, Ordering::SeqCst);
+        InFlight(busy.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// An earlier message of the conversation, sent again with every request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Message {
+    /// `user` or `assistant`.
+    pub role: String,
+    pub content: String,
+}
+
+#[derive(Serialize)]
+struct ChatMessage {
+    role: String,
+    content: MessageContent,
+}
+
+/// Plain text, or OpenAI-style content parts when images are attached.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+#[derive(Serialize)]
+struct ImageUrl {
+    url: String,
+}
+
+/// An image for a vision model, inlined as a `data:` URI.
+#[derive(Clone)]
+pub struct Image {
+    pub data_url: String,
+    pub bytes: usize,
+}
+
+impl Image {
+    pub fn load(path: &Path) -> Result<Image, String> {
+        let mime = match path.extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref() {
+            Some("png") => "image/png",
+            Some("jpg" | "jpeg") => "image/jpeg",
+            Some("gif") => "image/gif",
+            Some("webp") => "image/webp",
+            _ => return Err(format!("{} is not a PNG, JPEG, GIF or WebP image", path.display())),
+        };
+        let data = fs::read(path).map_err(|e| format!("Error reading image {}: {}", path.display(), e))?;
+        if data.len() > MAX_IMAGE_BYTES {
+            return Err(format!("{} is {} KB, over the {} KB limit", path.display(), data.len() / 1024, MAX_IMAGE_BYTES / 1024));
+        }
+        let encoded = base64::engine::general_purpose::STANDARD.encode(&data);
+        Ok(Image { data_url: format!("data:{};base64,{}", mime, encoded), bytes: data.len() })
+    }
+}
+
+#[derive(Serialize)]
+struct CompletionRequest {
+    model: String,
+    messages: Vec<ChatMessage>,
+    temperature: f32,
+    max_tokens: u32,
+    stream: bool,
+    stream_options: StreamOptions,
+}
+
+#[derive(Serialize)]
+struct StreamOptions {
+    include_usage: bool,
+}
+
+/// Appends `bytes` to `pending` and takes out the lines they complete.
+/// Works on raw bytes, so a character split across two chunks only decodes
+/// once its line is whole.
+fn take_lines(pending: &mut Vec<u8>, bytes: &[u8]) -> Vec<String> {
+    pending.extend_from_slice(bytes);
+    let mut lines = Vec::new();
+    while let Some(newline) = pending.iter().position(|b| *b == b'\n') {
+        let line: Vec<u8> = pending.drain(..=newline).collect();
+        lines.push(String::from_utf8_lossy(&line).into_owned());
+    }
+    lines
+}
+
+/// One `data:` event of a streamed completion.
+#[derive(Deserialize, Debug)]
+struct StreamChunk {
+    #[serde(default)]
+    choices: Vec<StreamChoice>,
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct StreamChoice {
+    delta: Delta,
+}
+
+#[derive(Deserialize, Debug)]
+struct Delta {
+    content: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Usage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+}
+
+#[derive(Deserialize)]
+struct ModelList {
+    data: Vec<ServedModel>,
+}
+
+/// A model listed by `/models`. `max_model_len` is a vLLM extension.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ServedModel {
+    pub id: String,
+    #[serde(default)]
+    pub max_model_len: Option<u32>,
+}
+
+/// What the server answered to `/models`, and how long it took.
+pub struct ServerInfo {
+    pub models: Vec<ServedModel>,
+    pub latency: Duration,
+}
+
+impl ServerInfo {
+    /// The entry for the model this client asks for, if it is served.
+    pub fn served(&self, model: &str) -> Option<&ServedModel> {
+        self.models.iter().find(|m| m.id == model)
+    }
+}
+
+/// The model's answer plus the token and timing numbers for the turn.
+pub struct Completion {
+    pub content: String,
+    pub usage: TurnUsage,
+}
+
+impl LlmClient {
+    /// The default profile: `[backend]` and `[limits]`.
+    pub fn new(config: &Config) -> Self {
+        LlmClient {
+            client: Client::new(),
+            profile: DEFAULT_PROFILE.to_string(),
+            api_url: config.api_url.value.clone(),
+            model_name: config.model.value.clone(),
+            base_temp: config.base_temperature.value,
+            max_temp: config.max_temperature.value,
+            max_tokens: config.max_tokens.value,
+            context_tokens: config.context_tokens.value,
+            prices: config.prices(),
+            busy: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn from_profile(profile: &ModelProfile, config: &Config) -> Self {
+        let base = LlmClient::new(config);
+        LlmClient {
+            profile: profile.name.clone(),
+            api_url: profile.api_url.clone().unwrap_or(base.api_url),
+            model_name: profile.model.clone(),
+            base_temp: profile.base_temperature.unwrap_or(base.base_temp),
+            max_temp: profile.max_temperature.unwrap_or(base.max_temp),
+            context_tokens: profile.context_tokens.unwrap_or(base.context_tokens),
+            prices: (
+                profile.cost_per_1k_prompt.unwrap_or(base.prices.0),
+                profile.cost_per_1k_completion.unwrap_or(base.prices.1),
+            ),
+            ..base
+        }
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model_name
+    }
+
+    pub fn prices(&self) -> (f64, f64) {
+        self.prices
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.busy.load(Ordering::SeqCst)
+    }
+
+    /// Counts a request on this client (and its clones) until the guard is dropped.
+    pub fn start_request(&self) -> InFlight {
+        InFlight::start(&self.busy)
+    }
+
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    /// Lists the served models (`GET /models`).
+    pub async fn server_info(&self) -> Result<ServerInfo, String> {
+        let started = Instant::now();
+        let response = self
+            .client
+            .get(format!("{}/models", self.api_url))
+            .timeout(PROBE_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| if e.is_timeout() { format!("no answer within {}s", PROBE_TIMEOUT.as_secs()) } else { e.to_string() })?;
+        let latency = started.elapsed();
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("/models answered HTTP {}", status));
+        }
+        let list: ModelList = response.json().await.map_err(|e| format!("/models answered something other than a model list: {}", e))?;
+        Ok(ServerInfo { models: list.data, latency })
+    }
+
+    /// Time for a minimal completion, prompt processing and all.
+    pub async fn ping(&self) -> Result<Duration, String> {
+        let client = LlmClient { max_tokens: 8, ..self.clone() };
+        let started = Instant::now();
+        client.chat_completion("Answer with one word.", &[], "Say OK.", &[], self.base_temp).await.map_err(|e| e.to_string())?;
+        Ok(started.elapsed())
+    }
+
+    /// Temperature for a loop iteration: it rises with every retry, up to `max_temp`.
+    pub fn calculate_temperature(&self, loop_count: u32, is_complex: bool) -> f32 {
+        let start_temp = if is_complex {
+            self.base_temp + 0.1
+        } else {
+            self.base_temp
+        };
+        let dynamic_temp = start_temp + (loop_count as f32 * 0.1);
+        if dynamic_temp > self.max_temp {
+            self.max_temp
+        } else {
+            dynamic_temp
+        }
+    }
+
+    /// `history` goes between the system prompt and `user_query`; `images`
+    /// go along with `user_query` (the model must support vision).
+    pub async fn chat_completion(
+        &self,
+        system_prompt: &str,
+        history: &[Message],
+        user_query: &str,
+        images: &[Image],
+        temperature: f32,
+    ) -> Result<Completion, Box<dyn std::error::Error + Send + Sync>> {
+        let user_content = if images.is_empty() {
+            MessageContent::Text(user_query.to_string())
+        } else {
+            let mut parts = vec![ContentPart::Text { text: user_query.to_string() }];
+            parts.extend(images.iter().map(|image| ContentPart::ImageUrl { image_url: ImageUrl { url: image.data_url.clone() } }));
+            MessageContent::Parts(parts)
+        };
+        let mut messages = vec![ChatMessage {
+            role: "system".to_string(),
+            content: MessageContent::Text(system_prompt.to_string()),
+        }];
+        messages.extend(history.iter().map(|message| ChatMessage {
+            role: message.role.clone(),
+            content: MessageContent::Text(message.content.clone()),
+        }));
+        messages.push(ChatMessage {
+            role: "user".to_string(),
+            content: user_content,
+        });
+
+        let request_body = CompletionRequest {
+            model: self.model_name.clone(),
+            messages,
+            temperature,
+            max_tokens: self.max_tokens,
+            // Streaming is what lets us measure time-to-first-token
+            stream: true,
+            stream_options: StreamOptions { include_usage: true },
+        };
+
+        let url = format!("{}/chat/completions", self.api_url);
+        let _in_flight = self.start_request();
+        let started = Instant::now();
+
+        let mut res = self.client
+            .post(&url)
+            .json(&request_body)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            let status = res.status();
+            let error_text = res.text().await?;
+            // vLLM answers 404 with "The model `x` does not exist." for a wrong name
+            if status == reqwest::StatusCode::NOT_FOUND && error_text.contains(&self.model_name) {
+                return Err(format!(
+                    "The server at {} does not serve the model {:?}. Run `rumi-cli doctor` to list the served models.",
+                    self.api_url, self.model_name
+                )
+                .into());
+            }
+            return Err(format!("API Error (HTTP {}): {}", status, error_text).into());
+        }
+
+        let mut content = String::new();
+        let mut usage = None;
+        let mut first_token = None;
+        let mut pending = Vec::new();
+
+        'stream: while let Some(bytes) = res.chunk().await? {
+            for line in take_lines(&mut pending, &bytes) {
+                let Some(data) = line.trim().strip_prefix("data:") else {
+                    continue;
+                };
+                let data = data.trim();
+                if data == "[DONE]" {
+                    break 'stream;
+                }
+                let Ok(chunk) = serde_json::from_str::<StreamChunk>(data) else {
+                    continue;
+                };
+                for choice in chunk.choices {
+                    if let Some(delta) = choice.delta.content.filter(|d| !d.is_empty()) {
+                        first_token.get_or_insert_with(|| started.elapsed());
+                        content.push_str(&delta);
+                    }
+                }
+                if chunk.usage.is_some() {
+                    usage = chunk.usage;
+                }
+            }
+        }
+
+        if content.is_empty() {
+            return Err("No content in response".into());
+        }
+
+        let latency = started.elapsed();
+        // Older servers ignore include_usage; fall back to a rough 4 chars per token
+        let (prompt_tokens, completion_tokens, estimated) = match usage {
+            Some(u) => (u.prompt_tokens, u.completion_tokens, false),
+            None => (
+                ((system_prompt.len() + history.iter().map(|m| m.content.len()).sum::<usize>() + user_query.len()) / 4) as u32,
+                (content.len() / 4) as u32,
+                true,
+            ),
+        };
+
+        Ok(Completion {
+            content,
+            usage: TurnUsage {
+                prompt_tokens,
+                completion_tokens,
+                estimated,
+                ttft_ms: first_token.unwrap_or(latency).as_millis() as u64,
+                latency_ms: latency.as_millis() as u64,
+                tool: None,
+                tool_ms: 0,
+                model: Some(self.model_name.clone()),
+                profile: Some(self.profile.clone()),
+                cost: (prompt_tokens as f64 * self.prices.0 + completion_tokens as f64 * self.prices.1) / 1000.0,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_split_across_chunks() {
+        let mut pending = Vec::new();
+        assert!(take_lines(&mut pending, b"data: {\"a\"").is_empty());
+        assert_eq!(take_lines(&mut pending, b": 1}\n\ndata: [DO"), ["data: {\"a\": 1}\n", "\n"]);
+        assert_eq!(take_lines(&mut pending, b"NE]\n"), ["data: [DONE]\n"]);
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_character_split_across_chunks() {
+        let line = "data: {\"content\": \"€𝄞\"}\n".as_bytes();
+        // Cut inside the three bytes of € and again inside the four of 𝄞
+        let euro = line.iter().position(|b| *b == 0xE2).unwrap();
+        let (first, rest) = line.split_at(euro + 1);
+        let (second, third) = rest.split_at(4);
+        let mut pending = Vec::new();
+        assert!(take_lines(&mut pending, first).is_empty());
+        assert!(take_lines(&mut pending, second).is_empty());
+        assert_eq!(take_lines(&mut pending, third), ["data: {\"content\": \"€𝄞\"}\n"]);
+    }
+}
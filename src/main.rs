@@ -1,302 +1,423 @@
-use dotenvy::dotenv;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
 use std::env;
 
-#[derive(Clone)]
-pub struct LlmClient {
-    client: Client,
-    api_url: String,
-    model_name: String,
-    base_temp: f32,
-    max_temp: f32,
-}
+mod agent;
+mod analysis;
+mod cli;
+mod compaction;
+mod config;
+mod custom_tools;
+mod diagnostics;
+mod docs;
+mod doctor;
+mod events;
+mod git;
+mod interrupt;
+mod llm;
+mod map_parser;
+mod mcp;
+mod memory;
+mod models;
+mod plan;
+mod process;
+mod prompts;
+mod redact;
+mod rpc;
+mod scheduler;
+mod session;
+mod symbols;
+mod tasks;
+mod tools;
+mod tui;
+mod usage;
+mod verify;
+
+use agent::Agent;
+use cli::{Command, Output};
+use config::Config;
+use efficiency_analyzer::state::AnalyzerState;
+use events::{AgentEvent, ChannelReporter, ConsoleReporter, JsonReporter, Reporter};
+use interrupt::Interrupt;
+use llm::Image;
+use mcp::McpTool;
+use map_parser::MapParser;
+use models::Models;
+use prompts::Prompts;
+use scheduler::{FileLocks, WorkUnit};
+use session::Session;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use tasks::TaskStatus;
+use usage::UsageTotals;
 
-#[derive(Serialize)]
-struct ChatMessage {
-    role: String,
-    content: String,
-}
+const DEFAULT_TASK: &str = "Analyze the map and tell me what the entry point of the application is.";
 
-#[derive(Serialize)]
-struct CompletionRequest {
-    model: String,
-    messages: Vec<ChatMessage>,
-    temperature: f32,
-    max_tokens: u32,
+#[tokio::main]
+async fn main() {
+    let invocation = match cli::parse(env::args().skip(1)) {
+        Ok(invocation) => invocation,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+    let config = match Config::load(&invocation.overrides) {
+        Ok(config) => Arc::new(config),
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+
+    match invocation.command {
+        Command::Run { task, plan, images } => {
+            let task = task.unwrap_or_else(|| DEFAULT_TASK.to_string());
+            let images = load_images(&images);
+            let agent = start_agent(&config, &task, plan, reporter(invocation.output, None))
+                .await
+                .with_images(images)
+                .with_interrupt(Interrupt::listen());
+            let mut agent = isolate(&config, agent).await;
+            let success = drive(&mut agent, &config, plan, invocation.output).await;
+            agent.finish(success);
+        }
+        Command::TasksList => list_tasks(),
+        Command::TasksRun { id, plan, jobs } => run_task(&config, &id, plan, jobs, invocation.output).await,
+        Command::ConfigShow => print!("{}", config.render()),
+        Command::Doctor => {
+            if !doctor::run(&config).await {
+                std::process::exit(1);
+            }
+        }
+        Command::Serve => rpc::serve(config).await,
+        Command::SessionMerge { id } => finish_session(&config, &id, true).await,
+        Command::SessionDiscard { id } => finish_session(&config, &id, false).await,
+    }
 }
 
-#[derive(Deserialize, Debug)]
-struct CompletionResponse {
-    choices: Vec<Choice>,
+async fn start_agent(config: &Arc<Config>, task: &str, plan_mode: bool, reporter: Arc<dyn Reporter>) -> Agent {
+    let (models, mut prompts, mcp_tools) = connect(config, plan_mode, reporter.as_ref()).await;
+    prompts.set("memory", memory::prompt_section(task, Path::new(".")));
+    Agent::new(models, prompts, Session::new(task), config.clone())
+        .with_mcp_tools(mcp_tools)
+        .with_reporter(reporter)
 }
 
-#[derive(Deserialize, Debug)]
-struct Choice {
-    message: MessageContent,
+/// Reads the `--image` files up front, so a bad path fails before the run starts.
+fn load_images(paths: &[String]) -> Vec<Image> {
+    paths
+        .iter()
+        .map(|path| {
+            Image::load(Path::new(path)).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            })
+        })
+        .collect()
 }
 
-#[derive(Deserialize, Debug)]
-struct MessageContent {
-    content: String,
+/// With `[git] isolate`, moves the agent into a fresh worktree on its own
+/// branch; the user merges or discards it afterwards.
+async fn isolate(config: &Config, mut agent: Agent) -> Agent {
+    if !config.isolate.value {
+        return agent;
+    }
+    let worktree = match git::create_worktree(&agent.session.id).await {
+        Ok(worktree) => worktree,
+        Err(e) => {
+            agent.report(AgentEvent::Error { text: format!("Failed to create a worktree: {}", e) });
+            std::process::exit(1);
+        }
+    };
+    agent.report(AgentEvent::Info { text: isolation_notice(&agent.session.id, &worktree) });
+    agent.session.worktree = Some(worktree.clone());
+    agent.save();
+    agent.with_workdir(worktree.path)
 }
 
-impl LlmClient {
-    pub fn new() -> Self {
-        dotenv().ok();
-        let api_url = env::var("VLLM_API_URL").expect("VLLM_API_URL must be set");
-        let model_name = env::var("MODEL_NAME").expect("MODEL_NAME must be set");
-        let base_temp = env::var("BASE_TEMPERATURE")
-            .unwrap_or("0.7".to_string())
-            .parse()
-            .unwrap_or(0.7);
-        let max_temp = env::var("MAX_TEMPERATURE")
-            .unwrap_or("1.2".to_string())
-            .parse()
-            .unwrap_or(1.2);
+fn isolation_notice(id: &str, worktree: &git::Worktree) -> String {
+    format!(
+        "Working on branch {} in {}. Finish with `rumi-cli session merge {}` or `rumi-cli session discard {}`.",
+        worktree.branch,
+        worktree.path.display(),
+        id,
+        id
+    )
+}
 
-        LlmClient {
-            client: Client::new(),
-            api_url,
-            model_name,
-            base_temp,
-            max_temp,
+/// `rumi session merge|discard <id>`.
+async fn finish_session(config: &Config, id: &str, merge: bool) {
+    let mut session = match Session::load(id) {
+        Ok(session) => session,
+        Err(e) => {
+            eprintln!("Failed to load session {}: {}", id, e);
+            std::process::exit(1);
         }
-    }
-
-    fn calculate_temperature(&self, loop_count: u32, is_complex: bool) -> f32 {
-        let start_temp = if is_complex {
-            self.base_temp + 0.1
-        } else {
-            self.base_temp
-        };
-        let dynamic_temp = start_temp + (loop_count as f32 * 0.1);
-        if dynamic_temp > self.max_temp {
-            self.max_temp
-        } else {
-            dynamic_temp
+    };
+    let Some(worktree) = session.worktree.take() else {
+        eprintln!("Session {} has no worktree (not isolated, or already merged/discarded).", id);
+        std::process::exit(1);
+    };
+    let result = if merge {
+        let message = config.commit_message(&format!("rumi session {}", id), &session.task, id);
+        git::merge_worktree(&worktree, &message).await
+    } else {
+        git::remove_worktree(&worktree).await.map(|()| format!("Discarded {}.", worktree.branch))
+    };
+    match result {
+        Ok(message) => {
+            println!("{}", message);
+            if let Err(e) = session.save() {
+                eprintln!("Failed to save session {}: {}", id, e);
+            }
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
     }
+}
 
-    pub async fn chat_completion(
-        &self,
-        system_prompt: &str,
-        user_query: &str,
-        loop_count: u32,
-        is_complex: bool,
-    ) -> Result<String, Box<dyn std::error::Error>> {
-        let temp = self.calculate_temperature(loop_count, is_complex);
-        println!("Thinking with Temp: {}, Attempt: {}", temp, loop_count + 1);
-
-        let messages = vec![
-            ChatMessage {
-                role: "system".to_string(),
-                content: system_prompt.to_string(),
-            },
-            ChatMessage {
-                role: "user".to_string(),
-                content: user_query.to_string(),
-            },
-        ];
-
-        let request_body = CompletionRequest {
-            model: self.model_name.clone(),
-            messages,
-            temperature: temp,
-            max_tokens: 2048,
-        };
-
-        let url = format!("{}/chat/completions", self.api_url);
-        
-        let res = self.client
-            .post(&url)
-            .json(&request_body)
-            .send()
-            .await?;
+/// Where progress goes for the chosen output mode. The TUI takes over once
+/// the agent runs (see `drive`); until then it prints like text mode.
+fn reporter(output: Output, unit: Option<usize>) -> Arc<dyn Reporter> {
+    match (output, unit) {
+        (Output::Json { interactive }, _) => Arc::new(JsonReporter::new(unit, interactive)),
+        (_, Some(id)) => Arc::new(ConsoleReporter::unit(id)),
+        (_, None) => Arc::new(ConsoleReporter::default()),
+    }
+}
 
-        if !res.status().is_success() {
-             let error_text = res.text().await?;
-             return Err(format!("API Error: {}", error_text).into());
-        }
+/// Runs the agent to the end, optionally behind the full-screen TUI.
+/// Returns whether it finished its task.
+async fn drive(agent: &mut Agent, config: &Config, plan: bool, output: Output) -> bool {
+    if output != Output::Tui {
+        return if plan { agent.run_planned().await } else { agent.run().await };
+    }
 
-        let response_json: CompletionResponse = res.json().await?;
-        
-        if let Some(choice) = response_json.choices.first() {
-            Ok(choice.message.content.clone())
-        } else {
-            Err("No content in response".into())
+    let (sender, receiver) = mpsc::channel();
+    let (approve, replies) = mpsc::channel();
+    let (instruct, instructions) = mpsc::channel();
+    let console = agent.set_reporter(Arc::new(ChannelReporter::new(sender, replies, instructions)));
+    let status = tui::Status::new(config);
+    let interrupt = agent.interrupt().clone();
+    let mut ui = tokio::task::spawn_blocking(move || tui::run(receiver, approve, instruct, interrupt, status));
+
+    let mut ui_result = None;
+    let success = tokio::select! {
+        success = async { if plan { agent.run_planned().await } else { agent.run().await } } => success,
+        // Quitting the UI early stops the agent mid-turn
+        result = &mut ui => {
+            ui_result = Some(result);
+            agent.save();
+            false
         }
+    };
+    // Dropping the channel reporter tells the UI that the run is over
+    agent.set_reporter(console);
+    let result = match ui_result {
+        Some(result) => result,
+        None => ui.await,
+    };
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => eprintln!("Terminal UI failed: {}", e),
+        Err(e) => eprintln!("Terminal UI crashed: {}", e),
     }
+    success
 }
 
-mod tools;
-
-mod map_parser;
-
-
-
-use tools::{execute_tool, ToolCall};
-
-use map_parser::MapParser;
-
-
-
-// ... existing LlmClient ...
-
-
-
-#[tokio::main]
-
-async fn main() {
-
-    println!("Rumi-CLI: Active and connected to vLLM (24k Context)");
-
-    let client = LlmClient::new();
-
-
+async fn connect(config: &Config, plan_mode: bool, reporter: &dyn Reporter) -> (Models, Prompts, Vec<McpTool>) {
+    let info = |text: String| reporter.report(AgentEvent::Info { text });
+    let mut models = Models::new(config);
+    // A problem is reported with its fix; the run still starts, the server may be coming up
+    for client in models.clients_mut() {
+        let probe = doctor::probe(client, config).await;
+        for finding in &probe.findings {
+            if let doctor::Finding::Warn { text, fix } | doctor::Finding::Fail { text, fix } = finding {
+                reporter.report(AgentEvent::Error { text: format!("{}. Fix: {}", text, fix) });
+            }
+        }
+        client.context_tokens = doctor::effective_context(client, config, probe.max_model_len);
+        if let Some(ms) = probe.latency_ms.filter(|_| !probe.failed()) {
+            info(format!(
+                "Rumi-CLI: connected to {} at {} ({}k context, {} ms)",
+                client.model(),
+                client.api_url(),
+                client.context_tokens / 1024,
+                ms
+            ));
+        }
+    }
+    if !config.roles.is_empty() {
+        let routes: Vec<String> = config.roles.iter().map(|(role, names)| format!("{} -> {}", role.name(), names.value.join(" | "))).collect();
+        info(format!("Model roles: {}", routes.join(", ")));
+    }
 
     // Load the Map
+    let project_map = MapParser::get_context_map(&config.map_path.value);
+    info(format!("Loaded {} ({} bytes)", config.map_path.value, project_map.len()));
+
+    // A server that fails to start is reported and left out
+    let mut mcp_tools = Vec::new();
+    for server in &config.mcp_servers {
+        match mcp::connect(&server.value) {
+            Ok(tools) => {
+                info(format!("Connected to MCP server {} ({} tools)", server.value.name, tools.len()));
+                mcp_tools.extend(tools);
+            }
+            Err(e) => reporter.report(AgentEvent::Error { text: format!("MCP server {}: {}", server.value.name, e) }),
+        }
+    }
 
-    let project_map = MapParser::get_context_map();
-
-    println!("Loaded MAP.md ({} bytes)", project_map.len());
-
-
-
-    let system_prompt = format!(r#"You are Rumi, a high-context coding agent.
-
-You operate in a Think -> Act -> Observe loop.
-
-
-
-# CODEBASE MAP
-
-The following is the authoritative map of the project. ONLY use file paths found in this map.
-
-{}
-
-
-
-# TOOL USAGE
-
-To perform actions, you MUST output a valid JSON object:
-
-{{
-
-  "tool": "read_file",
-
-  "args": {{ "path": "src/Main.res" }}
-
-}}
-
-OR
-
-{{
-
-  "tool": "write_file",
-
-  "args": {{ "path": "path/to/file", "content": "..." }}
-
-}}
-
-OR
-
-{{
-
-  "tool": "run_shell",
-
-  "args": {{ "command": "cargo check" }}
-
-}}
-
-
-
-# RULES
-
-1. Always explain your reasoning briefly before outputting the JSON tool call.
-
-2. Rely on the Codebase Map to find files. Do not guess paths.
-
-3. If you need to edit a file, read it first."#, project_map);
-
-
-
-    let mut user_query = String::from("Analyze the map and tell me what the entry point of the application is.");
-
-    let mut loop_count = 0;
-
-
-
-    loop {
-
-        match client.chat_completion(&system_prompt, &user_query, loop_count, false).await {
-
-            Ok(response) => {
-
-                println!("\n--- Rumi Thinks ---\n{}", response);
-
-
-
-                // Simple parser for JSON in response
-
-                if let Some(json_start) = response.find('{') {
-
-                    if let Some(json_end) = response.rfind('}') {
-
-                        let json_str = &response[json_start..json_end + 1];
-
-                        if let Ok(tool_call) = serde_json::from_str::<ToolCall>(json_str) {
-
-                            let result = execute_tool(tool_call);
-
-                            println!("\n--- Tool Execution ({}) ---\n{}", result.tool_name, result.output);
-
-                            
-
-                            // Feed the observation back into the next loop
-
-                            user_query = format!("Observation from {}:\n{}", result.tool_name, result.output);
-
-                            loop_count += 1;
-
-                            
-
-                            if loop_count > 5 {
-
-                                println!("Max loops reached. Stopping.");
-
-                                break;
-
-                            }
-
-                            continue;
+    let (prompts, overrides) = Prompts::load();
+    for result in overrides {
+        match result {
+            Ok(name) => info(format!("Using the {} prompt from {}", name, prompts::OVERRIDES_DIR)),
+            Err(e) => reporter.report(AgentEvent::Error { text: e }),
+        }
+    }
+    let prompts = prompts.with_project(config, &project_map, plan_mode, &mcp_tools);
+    (models, prompts, mcp_tools)
+}
 
-                        }
+fn list_tasks() {
+    let all = tasks::list(&[TaskStatus::Pending, TaskStatus::Active, TaskStatus::Postponed, TaskStatus::Completed]);
+    if all.is_empty() {
+        println!("No tasks found under tasks/. Run the _dev-system analyzer to generate some.");
+        return;
+    }
+    for task in all {
+        println!("{:>4}  {:<9}  {} ({} targets)", task.id, task.status.dir_name(), task.title, task.targets.len());
+    }
+}
 
+/// Runs the agent on an analyzer task and moves the task file through
+/// `pending` -> `active` -> `completed` (or `postponed` on failure).
+/// With `jobs > 1` every target is handed to its own sub-agent.
+async fn run_task(config: &Arc<Config>, id: &str, plan: bool, jobs: usize, output: Output) {
+    let Some(mut task) = tasks::find(id) else {
+        eprintln!("No pending, active or postponed task with id {}. See `rumi-cli tasks list`.", id);
+        std::process::exit(1);
+    };
+    let resumed = task.status == TaskStatus::Active;
+    if !resumed && let Err(e) = task.move_to(TaskStatus::Active) {
+        eprintln!("Failed to activate task {}: {}", task.id, e);
+        std::process::exit(1);
+    }
+    let reporter = reporter(output, None);
+    let info = |text: String| reporter.report(AgentEvent::Info { text });
+    let error = |text: String| reporter.report(AgentEvent::Error { text });
+    if resumed {
+        info(format!("Task {} was left active by an earlier run; running it again.", task.id));
+    }
+    info(format!("Running task {}: {}", task.id, task.title));
+    // Shared by every unit: Ctrl-C stops them all
+    let interrupt = Interrupt::listen();
+
+    // Overall success, plus (analyzer path, success) for every target. A single
+    // agent reports `done` itself once the task file has been moved.
+    let mut single = None;
+    let mut parallel_done = None;
+    let (success, results): (bool, Vec<(String, bool)>) = if jobs > 1 && task.targets.len() > 1 {
+        let (models, prompts, mcp_tools) = connect(config, plan, reporter.as_ref()).await;
+        let mut parent = Session::new(&task.to_prompt());
+        // Units share one worktree, so the whole task merges as one branch
+        let workdir = if config.isolate.value {
+            match git::create_worktree(&parent.id).await {
+                Ok(worktree) => {
+                    info(isolation_notice(&parent.id, &worktree));
+                    let path = worktree.path.clone();
+                    parent.worktree = Some(worktree);
+                    if let Err(e) = parent.save() {
+                        error(format!("Failed to save session: {}", e));
                     }
-
+                    path
+                }
+                Err(e) => {
+                    error(format!("Failed to create a worktree: {}", e));
+                    std::process::exit(1);
                 }
-
-                
-
-                println!("\nTask appears complete or no tool call found.");
-
-                break;
-
-            }
-
-            Err(e) => {
-
-                eprintln!("Error: {}", e);
-
-                break;
-
             }
-
+        } else {
+            PathBuf::from(".")
+        };
+        let locks = FileLocks::default();
+        let units = task
+            .targets
+            .iter()
+            .enumerate()
+            .map(|(i, target)| {
+                let id = i + 1;
+                let session = parent.child(id, &task.unit_prompt(target));
+                let mut unit_prompts = prompts.clone();
+                unit_prompts.set("memory", memory::prompt_section(&session.task, &workdir));
+                WorkUnit {
+                    id,
+                    file: target.path().to_string(),
+                    agent: Agent::new(models.clone(), unit_prompts, session, config.clone())
+                        .with_locks(locks.clone(), id)
+                        .with_workdir(workdir.clone())
+                        .with_mcp_tools(mcp_tools.clone())
+                        .with_interrupt(interrupt.clone())
+                        .with_reporter(self::reporter(output, Some(id))),
+                }
+            })
+            .collect();
+
+        let reports = scheduler::run_parallel(units, jobs, locks, plan).await;
+        let report = scheduler::render_report(&format!("Task {}: {}", task.id, task.title), &reports);
+        info(format!("\n{}", report));
+        let report_path = parent.path().with_extension("md");
+        match std::fs::write(&report_path, &report) {
+            Ok(()) => info(format!("Report saved to {}", report_path.display())),
+            Err(e) => error(format!("Failed to save report: {}", e)),
+        }
+        let mut totals = UsageTotals::default();
+        for unit in &reports {
+            totals.merge(&unit.totals);
         }
+        parallel_done = Some((report_path.display().to_string(), totals));
+
+        let results = task
+            .targets
+            .iter()
+            .enumerate()
+            .map(|(i, target)| {
+                let success = reports.iter().any(|r| r.id == i + 1 && r.success);
+                (target.analyzer_path.clone(), success)
+            })
+            .collect::<Vec<_>>();
+        (results.iter().all(|(_, success)| *success), results)
+    } else {
+        let agent = start_agent(config, &task.to_prompt(), plan, reporter.clone()).await.with_interrupt(interrupt);
+        let mut agent = isolate(config, agent).await;
+        let success = drive(&mut agent, config, plan, output).await;
+        single = Some(agent);
+        (success, task.targets.iter().map(|t| (t.analyzer_path.clone(), success)).collect())
+    };
+
+    let mut state = AnalyzerState::load_from(tasks::ANALYZER_STATE);
+    let action = format!("rumi task {}: {}", task.id, task.title);
+    for (path, success) in &results {
+        if *success {
+            state.record_action(path, &action);
+        } else {
+            state.mark_failure(path);
+        }
+    }
+    if let Err(e) = state.save_to(tasks::ANALYZER_STATE) {
+        error(format!("Failed to update {}: {}", tasks::ANALYZER_STATE, e));
+    }
 
+    let status = if success { TaskStatus::Completed } else { TaskStatus::Postponed };
+    match task.move_to(status) {
+        Ok(()) => info(format!("Task {} moved to {}", task.id, task.path.display())),
+        Err(e) => error(format!("Failed to move task {}: {}", task.id, e)),
     }
 
+    if let Some(agent) = single {
+        agent.finish(success);
+    } else if let Some((session, totals)) = parallel_done {
+        reporter.report(AgentEvent::Done { success, session, summary: String::new(), totals });
+    }
 }
-
-
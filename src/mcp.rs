@@ -0,0 +1,342 @@
+use crate::llm::Image;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+/// How long a server gets to answer `initialize` and `tools/list`.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often a waiting request checks whether it was cancelled.
+const CANCEL_POLL: Duration = Duration::from_millis(100);
+
+/// An MCP server declared under `[[mcp.servers]]` in rumi.toml:
+///
+/// ```toml
+/// [[mcp.servers]]
+/// name = "docs"
+/// command = "docs-index-mcp"
+/// args = ["--root", "docs"]
+/// ```
+///
+/// Its tools are offered to the model as `docs.<tool>`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct McpServerConfig {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Ask before every call to this server's tools.
+    #[serde(default)]
+    pub approve: bool,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout() -> u64 {
+    60
+}
+
+/// A running server: JSON-RPC over its stdin/stdout, one message per line.
+struct McpClient {
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines from stdout, read on a thread so requests can time out.
+    lines: Receiver<String>,
+    next_id: u64,
+    timeout: Duration,
+}
+
+impl McpClient {
+    fn spawn(server: &McpServerConfig) -> Result<Self, String> {
+        let mut child = Command::new(&server.command)
+            .args(&server.args)
+            .envs(&server.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            // Server logs would garble the console and the TUI
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {}", server.command, e))?;
+        let stdin = child.stdin.take().ok_or("no stdin")?;
+        let stdout = child.stdout.take().ok_or("no stdout")?;
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(McpClient { child, stdin, lines, next_id: 1, timeout: Duration::from_secs(server.timeout_secs) })
+    }
+
+    fn send(&mut self, message: Value) -> Result<(), String> {
+        writeln!(self.stdin, "{}", message).and_then(|_| self.stdin.flush()).map_err(|e| format!("Server closed its input: {}", e))
+    }
+
+    /// Sends a request and waits for its response, answering pings meanwhile.
+    /// When `cancelled` is set the server is told to drop the request, and the
+    /// client is free for the next one right away.
+    fn request(&mut self, method: &str, params: Value, timeout: Duration, cancelled: &AtomicBool) -> Result<Value, String> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            if cancelled.load(Ordering::Relaxed) {
+                let params = json!({ "requestId": id, "reason": "Cancelled by the user" });
+                self.send(json!({ "jsonrpc": "2.0", "method": "notifications/cancelled", "params": params }))?;
+                return Err("Cancelled".to_string());
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            let line = match self.lines.recv_timeout(left.min(CANCEL_POLL)) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) if left > CANCEL_POLL => continue,
+                Err(RecvTimeoutError::Timeout) => return Err(format!("{} timed out after {}s", method, timeout.as_secs())),
+                Err(RecvTimeoutError::Disconnected) => return Err("Server exited".to_string()),
+            };
+            // Anything that is not JSON is stray output; skip it
+            let Ok(message) = serde_json::from_str::<Value>(&line) else { continue };
+            match (message.get("id"), message.get("method")) {
+                (Some(request_id), Some(server_method)) => {
+                    let reply = if server_method == "ping" {
+                        json!({ "jsonrpc": "2.0", "id": request_id, "result": {} })
+                    } else {
+                        json!({ "jsonrpc": "2.0", "id": request_id, "error": { "code": -32601, "message": "Not supported by rumi" } })
+                    };
+                    self.send(reply)?;
+                }
+                (Some(response_id), None) if response_id.as_u64() == Some(id) => {
+                    if let Some(error) = message.get("error") {
+                        return Err(error["message"].as_str().unwrap_or("Unknown error").to_string());
+                    }
+                    return Ok(message["result"].clone());
+                }
+                // Notifications and stale responses
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Drop for McpClient {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Sets the flag when dropped, so a call whose future is dropped (Ctrl-C, or
+/// an editor cancelling the task) stops waiting on its blocking thread.
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// One tool of a connected server, callable from any agent sharing it.
+#[derive(Clone)]
+pub struct McpTool {
+    /// `server.tool`, the name the model calls it by.
+    pub name: String,
+    tool: String,
+    pub description: String,
+    pub input_schema: Value,
+    pub approve: bool,
+    client: Arc<Mutex<McpClient>>,
+}
+
+impl fmt::Debug for McpTool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("McpTool").field("name", &self.name).finish_non_exhaustive()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolListing {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    input_schema: Value,
+}
+
+impl McpTool {
+    /// Calls the tool. Text content becomes the output; images are added to
+    /// `images` so the model sees them with the next request.
+    pub async fn call(&self, args: Value, images: &mut Vec<Image>) -> (String, bool) {
+        // The client blocks on the server's stdout; keep it off the runtime
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let _guard = CancelOnDrop(cancelled.clone());
+        let tool = self.clone();
+        let call = tokio::task::spawn_blocking(move || {
+            let mut images = Vec::new();
+            (tool.call_blocking(&args, &mut images, &cancelled), images)
+        });
+        match call.await {
+            Ok((result, new_images)) => {
+                images.extend(new_images);
+                result
+            }
+            Err(e) => (format!("{} crashed: {}", self.name, e), false),
+        }
+    }
+
+    fn call_blocking(&self, args: &Value, images: &mut Vec<Image>, cancelled: &AtomicBool) -> (String, bool) {
+        let args = if args.is_null() { json!({}) } else { args.clone() };
+        if let Some(missing) = self.input_schema["required"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .find(|key| args.get(key).is_none())
+        {
+            return (format!("{} needs the {} arg", self.name, missing), false);
+        }
+
+        let mut client = self.client.lock().unwrap_or_else(|e| e.into_inner());
+        let timeout = client.timeout;
+        let result = match client.request("tools/call", json!({ "name": self.tool, "arguments": args }), timeout, cancelled) {
+            Ok(result) => result,
+            Err(e) => return (format!("{} failed: {}", self.name, e), false),
+        };
+
+        let mut output = Vec::new();
+        for part in result["content"].as_array().into_iter().flatten() {
+            match part["type"].as_str() {
+                Some("text") => output.push(part["text"].as_str().unwrap_or_default().to_string()),
+                Some("image") => {
+                    let (mime, data) = (part["mimeType"].as_str().unwrap_or("image/png"), part["data"].as_str().unwrap_or_default());
+                    images.push(Image { data_url: format!("data:{};base64,{}", mime, data), bytes: data.len() * 3 / 4 });
+                    output.push(format!("[{} image, attached to the next message]", mime));
+                }
+                Some("resource") => {
+                    let resource = &part["resource"];
+                    output.push(resource["text"].as_str().map(str::to_string).unwrap_or_else(|| format!("[resource {}]", resource["uri"])));
+                }
+                _ => output.push(part.to_string()),
+            }
+        }
+        let output = if output.is_empty() { "Success (no output)".to_string() } else { output.join("\n") };
+        (output, !result["isError"].as_bool().unwrap_or(false))
+    }
+
+    /// How the tool is shown to the model in the system prompt.
+    pub fn prompt_entry(&self) -> String {
+        let required: Vec<&str> = self.input_schema["required"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+        let args: serde_json::Map<String, Value> = self.input_schema["properties"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(name, schema)| {
+                let kind = schema["type"].as_str().unwrap_or("any");
+                let optional = if required.contains(&name.as_str()) { "" } else { ", optional" };
+                (name.clone(), Value::String(format!("<{}{}>", kind, optional)))
+            })
+            .collect();
+        let mut entry = format!("{{ \"tool\": \"{}\", \"args\": {} }}", self.name, Value::Object(args));
+        if !self.description.is_empty() {
+            entry.push_str(&format!("\n  {}", self.description.lines().next().unwrap_or_default()));
+        }
+        entry
+    }
+}
+
+/// Starts a server, performs the handshake and lists its tools.
+pub fn connect(server: &McpServerConfig) -> Result<Vec<McpTool>, String> {
+    let mut client = McpClient::spawn(server)?;
+    let cancelled = AtomicBool::new(false);
+    let params = json!({
+        "protocolVersion": PROTOCOL_VERSION,
+        "capabilities": {},
+        "clientInfo": { "name": "rumi", "version": env!("CARGO_PKG_VERSION") },
+    });
+    client.request("initialize", params, STARTUP_TIMEOUT, &cancelled)?;
+    client.send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))?;
+
+    let mut listings = Vec::new();
+    let mut cursor = None;
+    loop {
+        let params = cursor.map(|c| json!({ "cursor": c })).unwrap_or_else(|| json!({}));
+        let page = client.request("tools/list", params, STARTUP_TIMEOUT, &cancelled)?;
+        let tools: Vec<ToolListing> = serde_json::from_value(page["tools"].clone()).map_err(|e| format!("Invalid tools/list: {}", e))?;
+        listings.extend(tools);
+        match page["nextCursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+
+    let client = Arc::new(Mutex::new(client));
+    Ok(listings
+        .into_iter()
+        .map(|listing| McpTool {
+            name: format!("{}.{}", server.name, listing.name),
+            tool: listing.name,
+            description: listing.description,
+            input_schema: listing.input_schema,
+            approve: server.approve,
+            client: client.clone(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancelled_request_notifies_the_server() {
+        let log = std::env::temp_dir().join(format!("rumi-mcp-{}.log", std::process::id()));
+        // A server that never answers and logs what it receives
+        let server = McpServerConfig {
+            name: "slow".to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), format!("while read line; do echo \"$line\" >> '{}'; done", log.display())],
+            env: BTreeMap::new(),
+            approve: false,
+            timeout_secs: 60,
+        };
+        let mut client = McpClient::spawn(&server).unwrap();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let flag = cancelled.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            flag.store(true, Ordering::Relaxed);
+        });
+
+        let started = Instant::now();
+        let result = client.request("tools/call", json!({ "name": "wait" }), Duration::from_secs(60), &cancelled);
+        assert_eq!(result, Err("Cancelled".to_string()));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // Give the server time to log the notification before it is killed
+        let mut received = String::new();
+        while received.lines().count() < 2 && started.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(20));
+            received = std::fs::read_to_string(&log).unwrap_or_default();
+        }
+        drop(client);
+        let _ = std::fs::remove_file(&log);
+        let messages: Vec<Value> = received.lines().filter_map(|l| serde_json::from_str(l).ok()).collect();
+        assert_eq!(messages.len(), 2, "{}", received);
+        assert_eq!(messages[1]["method"], "notifications/cancelled");
+        assert_eq!(messages[1]["params"]["requestId"], messages[0]["id"]);
+    }
+}
@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MEMORY_DIR: &str = ".rumi/memory";
+const MEMORY_FILE: &str = ".rumi/memory/store.json";
+/// How many memories are pulled into the prompt at most.
+const MAX_RECALLED: usize = 5;
+/// Stale memories are dropped after this many seconds (30 days).
+const STALE_EXPIRY_SECS: u64 = 30 * 24 * 3600;
+
+/// Held from load to save, so parallel sub-agents remembering at the same
+/// time do not drop each other's memories.
+static STORE: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryKind {
+    #[default]
+    Fact,
+    Convention,
+    Mistake,
+}
+
+/// A file a memory talks about, with the content hash it had when the memory was written.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileStamp {
+    pub path: String,
+    pub hash: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MemoryEntry {
+    pub id: usize,
+    pub kind: MemoryKind,
+    pub text: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub files: Vec<FileStamp>,
+    pub created_at: u64,
+    /// Set when one of the referenced files changed after the memory was written.
+    #[serde(default)]
+    pub stale_since: Option<u64>,
+}
+
+/// Long-term project memory, persisted in `.rumi/memory/store.json`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct MemoryStore {
+    pub entries: Vec<MemoryEntry>,
+    /// Where the files the memories talk about are read from: `.`, or the
+    /// session's worktree when isolated.
+    #[serde(skip)]
+    root: PathBuf,
+}
+
+impl MemoryStore {
+    pub fn load(root: &Path) -> Self {
+        let _loading = STORE.lock().unwrap_or_else(|e| e.into_inner());
+        let mut store = Self::read(root);
+        if store.refresh() {
+            // Persist staleness so that expiry is measured from when the change was first seen
+            let _ = store.save();
+        }
+        store
+    }
+
+    fn read(root: &Path) -> Self {
+        let mut store: MemoryStore = fs::read_to_string(MEMORY_FILE)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        store.root = root.to_path_buf();
+        store
+    }
+
+    /// Replaces the file whole, so a concurrent `load` never reads half of it.
+    fn save(&self) -> std::io::Result<()> {
+        fs::create_dir_all(MEMORY_DIR)?;
+        let partial = Path::new(MEMORY_DIR).join("store.json.tmp");
+        fs::write(&partial, serde_json::to_string_pretty(self)?)?;
+        fs::rename(partial, MEMORY_FILE)
+    }
+
+    /// Adds a memory to the saved store and returns its id.
+    pub fn remember(root: &Path, kind: MemoryKind, text: String, tags: Vec<String>, files: Vec<String>) -> std::io::Result<usize> {
+        let _saving = STORE.lock().unwrap_or_else(|e| e.into_inner());
+        let mut store = Self::read(root);
+        store.refresh();
+        let id = store.add(kind, text, tags, files).id;
+        store.save()?;
+        Ok(id)
+    }
+
+    pub fn add(&mut self, kind: MemoryKind, text: String, tags: Vec<String>, files: Vec<String>) -> &MemoryEntry {
+        let id = self.entries.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+        self.entries.push(MemoryEntry {
+            id,
+            kind,
+            text,
+            tags: tags.into_iter().map(|t| t.trim().to_lowercase()).collect(),
+            files: files
+                .into_iter()
+                .map(|path| FileStamp { hash: hash_file(&self.root, &path), path })
+                .collect(),
+            created_at: now(),
+            stale_since: None,
+        });
+        &self.entries[self.entries.len() - 1]
+    }
+
+    /// Marks memories whose files changed as stale and drops the ones stale for too long.
+    /// Returns whether anything changed.
+    fn refresh(&mut self) -> bool {
+        let now = now();
+        let before = self.entries.len();
+        let mut changed = false;
+        for entry in &mut self.entries {
+            if entry.stale_since.is_none() && entry.files.iter().any(|f| hash_file(&self.root, &f.path) != f.hash) {
+                entry.stale_since = Some(now);
+                changed = true;
+            }
+        }
+        self.entries
+            .retain(|e| e.stale_since.is_none_or(|since| now.saturating_sub(since) < STALE_EXPIRY_SECS));
+        changed || self.entries.len() != before
+    }
+
+    /// The memories most relevant to `task`, by tag, keyword and file path overlap.
+    pub fn recall(&self, task: &str) -> Vec<&MemoryEntry> {
+        let task_lower = task.to_lowercase();
+        let words: Vec<&str> = task_lower
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|w| w.len() >= 3)
+            .collect();
+
+        let mut scored: Vec<(usize, &MemoryEntry)> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let text = entry.text.to_lowercase();
+                let mut score = 0;
+                score += entry.tags.iter().filter(|t| words.contains(&t.as_str())).count() * 3;
+                score += entry.files.iter().filter(|f| task_lower.contains(&f.path.to_lowercase())).count() * 3;
+                score += words.iter().filter(|w| text.contains(*w)).count();
+                if entry.stale_since.is_some() {
+                    score /= 2;
+                }
+                (score, entry)
+            })
+            .filter(|(score, _)| *score > 0)
+            .collect();
+
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.created_at.cmp(&a.1.created_at)));
+        scored.into_iter().take(MAX_RECALLED).map(|(_, e)| e).collect()
+    }
+}
+
+impl MemoryEntry {
+    pub fn render(&self) -> String {
+        let kind = match self.kind {
+            MemoryKind::Fact => "fact",
+            MemoryKind::Convention => "convention",
+            MemoryKind::Mistake => "past mistake",
+        };
+        let mut out = format!("- [{}] {}", kind, self.text);
+        if !self.files.is_empty() {
+            let paths: Vec<&str> = self.files.iter().map(|f| f.path.as_str()).collect();
+            out.push_str(&format!(" (files: {})", paths.join(", ")));
+        }
+        if self.stale_since.is_some() {
+            out.push_str(" [stale: the files changed since this was written, verify before relying on it]");
+        }
+        out
+    }
+}
+
+/// The `# PROJECT MEMORY` prompt section for a task working in `root`, or an
+/// empty string when nothing matches.
+pub fn prompt_section(task: &str, root: &Path) -> String {
+    let store = MemoryStore::load(root);
+    let recalled = store.recall(task);
+    if recalled.is_empty() {
+        return String::new();
+    }
+    let lines: Vec<String> = recalled.iter().map(|e| e.render()).collect();
+    format!("\n\n# PROJECT MEMORY\nThings learned in earlier sessions:\n{}", lines.join("\n"))
+}
+
+/// FNV-1a over the content of `path` under `root`; `None` when the file does not exist.
+fn hash_file(root: &Path, path: &str) -> Option<u64> {
+    let bytes = fs::read(root.join(path)).ok()?;
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    Some(hash)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stamps_follow_the_root() {
+        let root = std::env::temp_dir().join(format!("rumi-memory-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/lib.rs"), "fn a() {}").unwrap();
+
+        let mut store = MemoryStore { entries: Vec::new(), root: root.clone() };
+        store.add(MemoryKind::Fact, "a is private".to_string(), vec![], vec!["src/lib.rs".to_string()]);
+        assert!(store.entries[0].files[0].hash.is_some());
+        assert!(!store.refresh());
+
+        fs::write(root.join("src/lib.rs"), "pub fn a() {}").unwrap();
+        assert!(store.refresh());
+        assert!(store.entries[0].stale_since.is_some());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_recall_ranks_and_limits() {
+        let mut store = MemoryStore { entries: Vec::new(), root: std::env::temp_dir() };
+        let note = |text: &str| text.to_string();
+        store.add(MemoryKind::Fact, note("The CI image has no network access"), vec![], vec![]);
+        for i in 0..MAX_RECALLED + 2 {
+            store.add(MemoryKind::Fact, format!("Parser note {}", i), vec![], vec![]);
+        }
+        store.add(MemoryKind::Mistake, note("The parser hangs on unclosed braces"), vec![note("parser")], vec![note("src/parser.rs")]);
+
+        let recalled = store.recall("Fix the parser in src/parser.rs");
+        assert_eq!(recalled.len(), MAX_RECALLED);
+        assert_eq!(recalled[0].text, "The parser hangs on unclosed braces");
+        assert!(recalled.iter().all(|e| !e.text.contains("network")));
+        assert!(store.recall("Update README").is_empty());
+    }
+}
@@ -0,0 +1,175 @@
+use crate::config::Config;
+use crate::llm::LlmClient;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Name of the profile made of the `[backend]` settings themselves.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// A model the agent can talk to, declared as `[[backend.profiles]]`. Unset
+/// fields fall back to `[backend]` and `[limits]`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ModelProfile {
+    pub name: String,
+    pub model: String,
+    #[serde(default)]
+    pub api_url: Option<String>,
+    #[serde(default)]
+    pub context_tokens: Option<u32>,
+    #[serde(default)]
+    pub base_temperature: Option<f32>,
+    #[serde(default)]
+    pub max_temperature: Option<f32>,
+    #[serde(default)]
+    pub cost_per_1k_prompt: Option<f64>,
+    #[serde(default)]
+    pub cost_per_1k_completion: Option<f64>,
+}
+
+/// What a request is for; `[backend.roles]` maps each to profiles.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Writing the plan in `--plan` mode.
+    Planner,
+    /// The tool-call turns.
+    Coder,
+    /// The review before `finish` is accepted.
+    Verifier,
+    /// Compacting the history.
+    Summarizer,
+}
+
+impl Role {
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Planner => "planner",
+            Role::Coder => "coder",
+            Role::Verifier => "verifier",
+            Role::Summarizer => "summarizer",
+        }
+    }
+}
+
+/// One client per profile, and which of them serve each role. Clones share
+/// the clients' in-flight counts, so parallel sub-agents see each other's load.
+#[derive(Clone)]
+pub struct Models {
+    /// The default profile first.
+    clients: Vec<LlmClient>,
+    routes: BTreeMap<Role, Vec<usize>>,
+}
+
+impl Models {
+    /// Expects the profile names in `[backend.roles]` to have been checked by
+    /// `Config::load`.
+    pub fn new(config: &Config) -> Self {
+        let mut clients = vec![LlmClient::new(config)];
+        clients.extend(config.model_profiles.iter().map(|profile| LlmClient::from_profile(&profile.value, config)));
+        let routes = config
+            .roles
+            .iter()
+            .map(|(role, names)| {
+                let indices = names.value.iter().filter_map(|name| clients.iter().position(|c| c.profile == *name)).collect();
+                (*role, indices)
+            })
+            .collect();
+        Models { clients, routes }
+    }
+
+    pub fn clients(&self) -> &[LlmClient] {
+        &self.clients
+    }
+
+    pub fn clients_mut(&mut self) -> &mut [LlmClient] {
+        &mut self.clients
+    }
+
+    fn candidates(&self, role: Role) -> impl Iterator<Item = &LlmClient> {
+        let indices = self.routes.get(&role).filter(|indices| !indices.is_empty()).map_or(&[0][..], |i| i.as_slice());
+        indices.iter().map(|i| &self.clients[*i])
+    }
+
+    /// The client for a request in `role`: the first of its profiles with
+    /// nothing in flight, or the least busy one.
+    pub fn pick(&self, role: Role) -> LlmClient {
+        self.candidates(role)
+            .enumerate()
+            .min_by_key(|(order, client)| (client.in_flight(), *order))
+            .map(|(_, client)| client.clone())
+            .unwrap_or_else(|| self.clients[0].clone())
+    }
+
+    /// The smallest context among the profiles serving `role`, so whichever is
+    /// picked can take the request.
+    pub fn context_tokens(&self, role: Role) -> u32 {
+        self.candidates(role).map(|client| client.context_tokens).min().unwrap_or(self.clients[0].context_tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Setting, Source};
+
+    /// `[backend]` with a 32k context plus profiles of the given contexts,
+    /// all serving the coder.
+    fn models(contexts: &[u32]) -> Models {
+        let mut config = Config::default();
+        config.context_tokens.value = 32_768;
+        let mut names = Vec::new();
+        for (i, context) in contexts.iter().enumerate() {
+            let name = format!("p{}", i);
+            let profile = ModelProfile {
+                name: name.clone(),
+                model: format!("model-{}", i),
+                api_url: None,
+                context_tokens: Some(*context),
+                base_temperature: None,
+                max_temperature: None,
+                cost_per_1k_prompt: None,
+                cost_per_1k_completion: None,
+            };
+            config.model_profiles.push(Setting { value: profile, source: Source::Default });
+            names.push(name);
+        }
+        config.roles.insert(Role::Coder, Setting { value: names, source: Source::Default });
+        Models::new(&config)
+    }
+
+    #[test]
+    fn test_unrouted_roles_use_the_default_profile() {
+        let models = models(&[8192]);
+        assert_eq!(models.pick(Role::Verifier).profile, DEFAULT_PROFILE);
+        assert_eq!(models.context_tokens(Role::Verifier), 32_768);
+        assert_eq!(models.pick(Role::Coder).profile, "p0");
+
+        let mut config = Config::default();
+        config.roles.insert(Role::Coder, Setting { value: Vec::new(), source: Source::Default });
+        assert_eq!(Models::new(&config).pick(Role::Coder).profile, DEFAULT_PROFILE);
+    }
+
+    #[test]
+    fn test_the_least_busy_profile_is_picked() {
+        let models = models(&[8192, 8192, 8192]);
+        let first = models.pick(Role::Coder).start_request();
+        assert_eq!(models.pick(Role::Coder).profile, "p1");
+        let _second = models.pick(Role::Coder).start_request();
+        let _third = models.pick(Role::Coder).start_request();
+        // `clients()` starts with the default profile, so this is p1
+        let _again = models.clients()[2].start_request();
+        // p0 and p2 have one request each; the earlier profile wins the tie
+        assert_eq!(models.pick(Role::Coder).profile, "p0");
+        drop(first);
+        assert_eq!(models.pick(Role::Coder).profile, "p0");
+        assert_eq!(models.clients()[2].in_flight(), 2);
+    }
+
+    #[test]
+    fn test_context_is_the_smallest_of_the_candidates() {
+        let models = models(&[16_384, 4096, 65_536]);
+        assert_eq!(models.context_tokens(Role::Coder), 4096);
+        assert_eq!(models.context_tokens(Role::Planner), 32_768);
+    }
+}
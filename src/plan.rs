@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Pending,
+    Done,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlanStep {
+    pub description: String,
+    pub status: StepStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// The numbered step plan the agent keeps while working in plan-and-execute mode.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Plan {
+    pub steps: Vec<PlanStep>,
+}
+
+impl Plan {
+    /// Replaces the plan with a new list of steps.
+    /// Steps that were already completed keep their status if the model repeats them.
+    pub fn update(&mut self, steps: Vec<String>) -> String {
+        let previous = std::mem::take(&mut self.steps);
+        for description in steps {
+            let description = description.trim().to_string();
+            if description.is_empty() {
+                continue;
+            }
+            let done = previous
+                .iter()
+                .find(|s| s.status == StepStatus::Done && s.description == description);
+            self.steps.push(PlanStep {
+                status: if done.is_some() { StepStatus::Done } else { StepStatus::Pending },
+                note: done.and_then(|s| s.note.clone()),
+                description,
+            });
+        }
+        format!("Plan updated ({} steps).\n{}", self.steps.len(), self.render())
+    }
+
+    /// Marks a 1-based step as done.
+    pub fn complete_step(&mut self, step: usize, note: Option<String>) -> Result<String, String> {
+        let total = self.steps.len();
+        match step.checked_sub(1).and_then(|i| self.steps.get_mut(i)) {
+            Some(entry) => {
+                entry.status = StepStatus::Done;
+                entry.note = note;
+                Ok(format!("Step {} marked as done.\n{}", step, self.render()))
+            }
+            None => Err(format!("Step {} does not exist (plan has {} steps).", step, total)),
+        }
+    }
+
+    /// Returns the first step that is not done yet, with its 1-based number.
+    pub fn current(&self) -> Option<(usize, &PlanStep)> {
+        self.steps
+            .iter()
+            .enumerate()
+            .find(|(_, s)| s.status != StepStatus::Done)
+            .map(|(i, s)| (i + 1, s))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn render(&self) -> String {
+        let current = self.current().map(|(n, _)| n);
+        let mut out = String::new();
+        for (i, step) in self.steps.iter().enumerate() {
+            let marker = match step.status {
+                StepStatus::Done => "[x]",
+                StepStatus::Pending if current == Some(i + 1) => "[>]",
+                StepStatus::Pending => "[ ]",
+            };
+            out.push_str(&format!("{}. {} {}", i + 1, marker, step.description));
+            if let Some(note) = &step.note {
+                out.push_str(&format!(" ({})", note));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(steps: &[&str]) -> Plan {
+        let mut plan = Plan::default();
+        plan.update(steps.iter().map(|s| s.to_string()).collect());
+        plan
+    }
+
+    #[test]
+    fn test_complete_step_advances_current() {
+        let mut plan = plan(&["Read main", "Edit main", "Run check"]);
+        assert_eq!(plan.current().map(|(n, _)| n), Some(1));
+
+        plan.complete_step(1, Some("found it".to_string())).unwrap();
+        assert_eq!(plan.current().map(|(n, s)| (n, s.description.as_str())), Some((2, "Edit main")));
+        assert_eq!(plan.render(), "1. [x] Read main (found it)\n2. [>] Edit main\n3. [ ] Run check\n");
+
+        plan.complete_step(3, None).unwrap();
+        plan.complete_step(2, None).unwrap();
+        assert!(plan.current().is_none());
+    }
+
+    #[test]
+    fn test_complete_step_out_of_range() {
+        let mut plan = plan(&["Only step"]);
+        assert_eq!(plan.complete_step(0, None).unwrap_err(), "Step 0 does not exist (plan has 1 steps).");
+        assert!(plan.complete_step(2, None).is_err());
+        assert_eq!(plan.current().map(|(n, _)| n), Some(1));
+    }
+
+    #[test]
+    fn test_update_keeps_repeated_done_steps() {
+        let mut plan = plan(&["Read main", "Edit main"]);
+        plan.complete_step(1, Some("note".to_string())).unwrap();
+
+        plan.update(vec![" Read main ".to_string(), String::new(), "Edit lib".to_string()]);
+        assert_eq!(plan.steps.len(), 2);
+        assert_eq!(plan.steps[0].status, StepStatus::Done);
+        assert_eq!(plan.steps[0].note.as_deref(), Some("note"));
+        assert_eq!(plan.steps[1].status, StepStatus::Pending);
+        assert_eq!(plan.current().map(|(n, _)| n), Some(2));
+    }
+}
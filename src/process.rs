@@ -0,0 +1,33 @@
+use std::io;
+use std::process::{Output, Stdio};
+use tokio::process::Command;
+
+/// Runs `command` to completion and collects stdout and stderr, like
+/// `std::process::Command::output`. The child gets a process group of its
+/// own; when the returned future is dropped (Ctrl-C, or an editor cancelling
+/// the task) the whole group is killed, so `sh -c` leaves nothing running.
+pub async fn output(mut command: Command) -> io::Result<Output> {
+    command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).kill_on_drop(true);
+    #[cfg(unix)]
+    command.process_group(0);
+    let child = command.spawn()?;
+    let mut group = GroupGuard(child.id());
+    let output = child.wait_with_output().await;
+    group.0 = None;
+    output
+}
+
+/// Kills the process group of a child that is still being waited on.
+struct GroupGuard(Option<u32>);
+
+impl Drop for GroupGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.0 {
+            // SAFETY: killpg only sends a signal; the group was created for this child
+            unsafe {
+                libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+            }
+        }
+    }
+}
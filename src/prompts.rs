@@ -0,0 +1,270 @@
+use crate::config::Config;
+use crate::mcp::McpTool;
+use crate::tools::ToolCall;
+use std::fs;
+use std::path::Path;
+
+/// A file named after a template here replaces the built-in one.
+pub const OVERRIDES_DIR: &str = ".rumi/prompts";
+
+/// Slots of the system prompt. The optional sections (`fragments`, `memory`,
+/// `state`, `plan`) bring their own heading and are empty when there is
+/// nothing to show.
+const SYSTEM_SLOTS: &[&str] = &["map", "tools", "rules", "fragments", "memory", "state", "plan"];
+
+/// Built-in rules, with the tools each one is about; rules whose tools are
+/// all disabled are left out.
+const RULES: &[(&[&str], &str)] = &[
+    (&[], "Always explain your reasoning briefly before outputting the JSON tool call."),
+    (&[], "Rely on the Codebase Map to find files. Do not guess paths."),
+    (&[], "If you need to edit a file, read it first."),
+    (&["check"], "After editing code, use the check tool to compile it and fix the reported errors."),
+    (&["remember"], "When you learn a project convention or make a mistake worth avoiding next time, save it with remember."),
+    (
+        &["git_status", "git_diff", "git_log", "git_blame", "git_commit"],
+        "Use the git_* tools for git, not run_shell. Commits are shown to the user for approval.",
+    ),
+    (
+        &["list_symbols", "find_definition", "find_references"],
+        "Navigate by symbol with list_symbols, find_definition and find_references instead of reading whole files to find code.",
+    ),
+    (&["analyze_file"], "When refactoring for the analyzer, run analyze_file after your edits to confirm the drag is under target."),
+    (&["docs_lookup"], "Look APIs up in the project references with docs_lookup before guessing them."),
+    (
+        &[],
+        "Every reply must contain exactly one tool call. When the task is done, call finish with a summary; the work may be checked before the run ends.",
+    ),
+];
+
+/// A prompt with `{{slot}}` placeholders.
+#[derive(Clone)]
+struct Template {
+    text: String,
+    slots: &'static [&'static str],
+}
+
+impl Template {
+    /// Fills every slot in one pass, so a value that happens to contain
+    /// `{{plan}}` is left alone. Slots without a value render empty.
+    fn render(&self, values: &[(&str, &str)]) -> String {
+        let mut out = String::new();
+        let mut rest = self.text.as_str();
+        while let Some(open) = rest.find("{{") {
+            let Some(close) = rest[open..].find("}}").map(|c| open + c) else { break };
+            out.push_str(&rest[..open]);
+            let name = rest[open + 2..close].trim();
+            match values.iter().find(|(slot, _)| *slot == name) {
+                Some((_, value)) => out.push_str(value),
+                None if self.slots.contains(&name) => {}
+                None => out.push_str(&rest[open..close + 2]),
+            }
+            rest = &rest[close + 2..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// The first slot that this template does not have.
+    fn unknown_slot(&self) -> Option<&str> {
+        let mut rest = self.text.as_str();
+        while let Some(open) = rest.find("{{") {
+            let close = open + rest[open..].find("}}")?;
+            let name = rest[open + 2..close].trim();
+            if !self.slots.contains(&name) {
+                return Some(name);
+            }
+            rest = &rest[close + 2..];
+        }
+        None
+    }
+}
+
+/// The prompts of a run, and the system prompt slots that stay the same for
+/// all of its turns.
+#[derive(Clone)]
+pub struct Prompts {
+    system: Template,
+    planning: Template,
+    step: Template,
+    summarizer: Template,
+    reviewer: Template,
+    fixed: Vec<(&'static str, String)>,
+}
+
+impl Prompts {
+    /// The built-in templates, each replaced by `.rumi/prompts/<name>.md` when
+    /// that exists. Returns the overrides used, and an error for each override
+    /// that was skipped because it has a slot the template does not.
+    pub fn load() -> (Prompts, Vec<Result<&'static str, String>>) {
+        let mut overrides = Vec::new();
+        let mut load = |name, builtin: &str, slots| load_template(name, builtin, slots, &mut overrides);
+        let prompts = Prompts {
+            system: load("system", include_str!("prompts/system.md"), SYSTEM_SLOTS),
+            planning: load("planning", include_str!("prompts/planning.md"), &["task"]),
+            step: load("step", include_str!("prompts/step.md"), &["number", "step"]),
+            summarizer: load("summarizer", include_str!("prompts/summarizer.md"), &[]),
+            reviewer: load("reviewer", include_str!("prompts/reviewer.md"), &[]),
+            fixed: Vec::new(),
+        };
+        (prompts, overrides)
+    }
+
+    /// Sets a system prompt slot for the whole run.
+    pub fn set(&mut self, slot: &'static str, value: String) {
+        self.fixed.retain(|(s, _)| *s != slot);
+        self.fixed.push((slot, value));
+    }
+
+    /// Fills `map`, `tools`, `rules` and `fragments` from the config.
+    pub fn with_project(mut self, config: &Config, project_map: &str, plan_mode: bool, mcp_tools: &[McpTool]) -> Self {
+        self.set("map", project_map.to_string());
+        self.set("tools", tool_docs(config, plan_mode, mcp_tools));
+        self.set("rules", rules(config));
+        self.set("fragments", config.prompt_fragments.iter().map(|f| format!("\n\n{}", f.value)).collect());
+        self
+    }
+
+    /// The system prompt for a turn; `state` and `plan` change as the session goes.
+    pub fn system(&self, state: &str, plan: &str) -> String {
+        let mut values: Vec<(&str, &str)> = self.fixed.iter().map(|(slot, value)| (*slot, value.as_str())).collect();
+        values.extend([("state", state), ("plan", plan)]);
+        self.system.render(&values)
+    }
+
+    pub fn planning(&self, task: &str) -> String {
+        self.planning.render(&[("task", task)])
+    }
+
+    pub fn step(&self, number: usize, step: &str) -> String {
+        self.step.render(&[("number", &number.to_string()), ("step", step)])
+    }
+
+    pub fn summarizer(&self) -> String {
+        self.summarizer.render(&[])
+    }
+
+    pub fn reviewer(&self) -> String {
+        self.reviewer.render(&[])
+    }
+}
+
+fn load_template(
+    name: &'static str,
+    builtin: &str,
+    slots: &'static [&'static str],
+    overrides: &mut Vec<Result<&'static str, String>>,
+) -> Template {
+    let builtin = Template { text: builtin.trim_end().to_string(), slots };
+    let path = Path::new(OVERRIDES_DIR).join(format!("{}.md", name));
+    let Ok(text) = fs::read_to_string(&path) else { return builtin };
+    let custom = Template { text: text.trim_end().to_string(), slots };
+    if let Some(slot) = custom.unknown_slot() {
+        let known = if slots.is_empty() { "none".to_string() } else { slots.join(", ") };
+        overrides.push(Err(format!(
+            "{}: unknown slot {{{{{}}}}} (the {} prompt has: {}); using the built-in prompt",
+            path.display(),
+            slot,
+            name,
+            known
+        )));
+        return builtin;
+    }
+    overrides.push(Ok(name));
+    custom
+}
+
+/// The `tools` slot: the built-in tools this run offers, then the project
+/// and MCP tools, leaving out disabled ones.
+fn tool_docs(config: &Config, plan_mode: bool, mcp_tools: &[McpTool]) -> String {
+    let offered = |call: &ToolCall| match call {
+        ToolCall::FetchUrl { .. } => config.fetch_urls.value,
+        ToolCall::UpdatePlan { .. } | ToolCall::CompleteStep { .. } => plan_mode,
+        _ => true,
+    };
+    let builtin: Vec<String> = ToolCall::examples()
+        .iter()
+        .filter(|call| offered(call) && !config.is_tool_disabled(call.name()))
+        .map(ToolCall::prompt_entry)
+        .collect();
+    format!("{}{}{}", builtin.join("\n"), project_tools(config), mcp_section(config, mcp_tools))
+}
+
+/// The `[[tools.custom]]` tools that are not disabled.
+fn project_tools(config: &Config) -> String {
+    let entries: Vec<String> = config
+        .custom_tools
+        .iter()
+        .filter(|t| !config.is_tool_disabled(&t.value.name))
+        .map(|t| t.value.prompt_entry())
+        .collect();
+    if entries.is_empty() {
+        return String::new();
+    }
+    format!("\n\n# PROJECT TOOLS\nDefined for this project; call them like the tools above.\n{}", entries.join("\n"))
+}
+
+/// The tools of the connected MCP servers that are not disabled.
+fn mcp_section(config: &Config, mcp_tools: &[McpTool]) -> String {
+    let entries: Vec<String> = mcp_tools
+        .iter()
+        .filter(|t| !config.is_tool_disabled(&t.name))
+        .map(McpTool::prompt_entry)
+        .collect();
+    if entries.is_empty() {
+        return String::new();
+    }
+    format!("\n\n# MCP TOOLS\nProvided by external servers as `server.tool`; call them like the tools above.\n{}", entries.join("\n"))
+}
+
+/// The `rules` slot: the built-in rules that apply, the project rules from
+/// rumi.toml and the disabled tools, numbered together.
+fn rules(config: &Config) -> String {
+    let mut rules: Vec<String> = RULES
+        .iter()
+        .filter(|(tools, _)| tools.is_empty() || tools.iter().any(|tool| !config.is_tool_disabled(tool)))
+        .map(|(_, rule)| rule.to_string())
+        .collect();
+    rules.extend(config.rules.iter().map(|rule| rule.value.clone()));
+    if !config.disabled_tools.value.is_empty() {
+        rules.push(format!("These tools are disabled in this project, do not call them: {}", config.disabled_tools.value.join(", ")));
+    }
+    rules.iter().enumerate().map(|(i, rule)| format!("{}. {}", i + 1, rule)).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SLOTS: &[&str] = &["task", "plan"];
+
+    fn template(text: &str) -> Template {
+        Template { text: text.to_string(), slots: SLOTS }
+    }
+
+    #[test]
+    fn test_render_fills_slots_in_one_pass() {
+        let template = template("Do {{ task }}.\n{{plan}}");
+        assert_eq!(template.render(&[("task", "step {{plan}}"), ("plan", "1. read")]), "Do step {{plan}}.\n1. read");
+    }
+
+    #[test]
+    fn test_render_leaves_unknown_placeholders() {
+        let template = template("{{task}} in {{ lang }} {{plan}} {{unclosed");
+        assert_eq!(template.render(&[("task", "fix")]), "fix in {{ lang }}  {{unclosed");
+        assert_eq!(template.unknown_slot(), Some("lang"));
+    }
+
+    #[test]
+    fn test_rules_of_disabled_tools_are_left_out() {
+        let mut config = Config::default();
+        assert!(rules(&config).contains("git_* tools"));
+        config.disabled_tools.value = vec!["git_commit".to_string(), "list_symbols".to_string()];
+        assert!(rules(&config).contains("git_* tools"));
+        assert!(rules(&config).contains("Navigate by symbol"));
+
+        config.disabled_tools.value = ["git_status", "git_diff", "git_log", "git_blame", "git_commit"].map(String::from).to_vec();
+        let rules = rules(&config);
+        assert!(!rules.contains("git_* tools"));
+        assert!(rules.ends_with("do not call them: git_status, git_diff, git_log, git_blame, git_commit"));
+    }
+}
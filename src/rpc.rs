@@ -0,0 +1,456 @@
+use crate::config::Config;
+use crate::events::{event_json, AgentEvent, Reporter};
+use crate::interrupt::Interrupt;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{self, Path};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use tokio::task::JoinHandle;
+
+// JSON-RPC error codes
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const REQUEST_CANCELLED: i64 = -32800;
+
+/// JSON-RPC 2.0 over stdio with LSP `Content-Length` framing. Requests from
+/// rumi to the editor (approvals, workspace edits) block until answered.
+struct Transport {
+    writer: Mutex<io::Stdout>,
+    next_id: AtomicU64,
+    /// Requests waiting for the editor, with the task that sent them.
+    pending: Mutex<HashMap<u64, (String, mpsc::Sender<Value>)>>,
+}
+
+impl Transport {
+    fn send(&self, message: Value) {
+        let body = message.to_string();
+        let mut out = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let written = write!(out, "Content-Length: {}\r\n\r\n{}", body.len(), body).and_then(|_| out.flush());
+        if let Err(e) = written {
+            eprintln!("Failed to write to the editor: {}", e);
+        }
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn respond(&self, id: Value, result: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+    }
+
+    fn respond_error(&self, id: Value, code: i64, message: &str) {
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }));
+    }
+
+    /// Sends a request to the editor for `task` and waits for its result;
+    /// `None` if the editor answered with an error, went away or the task
+    /// was cancelled. The wait hands the worker thread's other tasks to the
+    /// rest of the runtime.
+    fn request(&self, task: &str, cancelled: &AtomicBool, method: &str, params: Value) -> Option<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = mpsc::channel();
+        {
+            // Checked under the lock `cancel` takes, so a cancel never misses a request
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            if cancelled.load(Ordering::SeqCst) {
+                return None;
+            }
+            pending.insert(id, (task.to_string(), sender));
+        }
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        let response = tokio::task::block_in_place(|| receiver.recv()).ok()?;
+        response.get("result").cloned()
+    }
+
+    fn resolve(&self, response: Value) {
+        let Some(id) = response["id"].as_u64() else {
+            return;
+        };
+        if let Some((_, sender)) = self.pending.lock().unwrap_or_else(|e| e.into_inner()).remove(&id) {
+            let _ = sender.send(response);
+        }
+    }
+
+    /// Fails the requests `task` is waiting on, so it gets back to an await
+    /// point where it can be aborted.
+    fn cancel(&self, task: &str) {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).retain(|_, (owner, _)| owner != task);
+    }
+
+    /// Fails every request still waiting for the editor.
+    fn close(&self) {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+/// Reads one framed message; `Ok(None)` at end of input.
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Progress, approvals and writes of one task, routed to the editor.
+struct RpcReporter {
+    transport: Arc<Transport>,
+    /// Id of the `rumi/startTask` request, so the editor can tell tasks apart.
+    request: Value,
+    /// Set by `$/cancelRequest`: nothing is reported, approved or written after it.
+    cancelled: Arc<AtomicBool>,
+}
+
+impl RpcReporter {
+    fn request(&self, method: &str, params: Value) -> Option<Value> {
+        self.transport.request(&self.request.to_string(), &self.cancelled, method, params)
+    }
+}
+
+impl Reporter for RpcReporter {
+    fn report(&self, event: AgentEvent) {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return;
+        }
+        if let Some(event) = event_json(event) {
+            self.transport.notify("rumi/progress", json!({ "request": self.request, "event": event }));
+        }
+    }
+
+    fn approve(&self, tool: &str, summary: &str) -> bool {
+        let params = json!({ "request": self.request, "tool": tool, "summary": summary });
+        let result = self.request("rumi/approve", params);
+        result.and_then(|r| r["approved"].as_bool()).unwrap_or(false) && !self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Every write becomes a `workspace/applyEdit`; applying it is the approval.
+    fn apply_write(&self, path: &str, content: &str) -> Option<Result<String, String>> {
+        let edit = match workspace_edit(path, content) {
+            Ok(edit) => edit,
+            Err(e) => return Some(Err(e)),
+        };
+        let params = json!({ "label": format!("rumi: write {}", path), "edit": edit });
+        let outcome = match self.request("workspace/applyEdit", params) {
+            Some(result) if result["applied"].as_bool() == Some(true) => Ok(format!("The editor applied the edit to {}", path)),
+            Some(result) => Err(format!(
+                "The editor rejected the edit to {}{}. Do not retry it unchanged.",
+                path,
+                result["failureReason"].as_str().map(|r| format!(": {}", r)).unwrap_or_default()
+            )),
+            None => Err(format!("The editor did not apply the edit to {}", path)),
+        };
+        Some(outcome)
+    }
+
+    /// After `rumi/interrupt`, asks the editor with `rumi/followUp`; an
+    /// empty `text` stops the task.
+    fn follow_up(&self) -> Option<String> {
+        let result = self.request("rumi/followUp", json!({ "request": self.request }))?;
+        Some(result["text"].as_str().unwrap_or_default().trim().to_string()).filter(|text| !text.is_empty())
+    }
+}
+
+/// Replaces the whole document, or creates it when it does not exist yet.
+fn workspace_edit(path: &str, content: &str) -> Result<Value, String> {
+    let uri = path_to_uri(path);
+    let old = match fs::read_to_string(path) {
+        Ok(old) => old,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let start = json!({ "line": 0, "character": 0 });
+            return Ok(json!({ "documentChanges": [
+                { "kind": "create", "uri": uri },
+                {
+                    "textDocument": { "uri": uri, "version": null },
+                    "edits": [{ "range": { "start": start, "end": start }, "newText": content }],
+                },
+            ]}));
+        }
+        // Creating over a file we cannot read would clobber it
+        Err(e) => return Err(format!("Cannot read {} to replace it: {}", path, e)),
+    };
+    // LSP positions count UTF-16 code units
+    let last_line = old.rsplit('\n').next().unwrap_or("");
+    let end = json!({ "line": old.matches('\n').count(), "character": last_line.encode_utf16().count() });
+    let edit = json!({ "range": { "start": { "line": 0, "character": 0 }, "end": end }, "newText": content });
+    let mut changes = Map::new();
+    changes.insert(uri, json!([edit]));
+    Ok(json!({ "changes": changes }))
+}
+
+fn path_to_uri(path: &str) -> String {
+    let absolute = path::absolute(path).unwrap_or_else(|_| Path::new(path).to_path_buf());
+    format!("file://{}", percent_encode(&absolute.to_string_lossy()))
+}
+
+/// `file:///abs/src/a.rs` -> `src/a.rs` when it is inside the working directory.
+fn uri_to_path(uri: &str) -> String {
+    let path = percent_decode(uri.strip_prefix("file://").unwrap_or(uri));
+    let relative = env::current_dir().ok().and_then(|cwd| Path::new(&path).strip_prefix(cwd).ok().map(Path::to_path_buf));
+    relative.map(|p| p.display().to_string()).unwrap_or(path)
+}
+
+/// Escapes every byte a URI path cannot hold as is; `/` stays.
+fn percent_encode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for &byte in text.as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[derive(Deserialize)]
+struct Position {
+    line: u32,
+}
+
+#[derive(Deserialize)]
+struct Range {
+    start: Position,
+    end: Position,
+}
+
+/// Params of `rumi/startTask`. `uri` and `selection` scope the task to what
+/// the user has open in the editor.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StartTask {
+    task: String,
+    #[serde(default)]
+    uri: Option<String>,
+    #[serde(default)]
+    selection: Option<Range>,
+    #[serde(default)]
+    selected_text: Option<String>,
+    #[serde(default)]
+    plan: bool,
+}
+
+impl StartTask {
+    fn prompt(&self) -> String {
+        let Some(uri) = &self.uri else {
+            return self.task.clone();
+        };
+        let mut prompt = format!("{}\n\nThe user is working in {}.", self.task, uri_to_path(uri));
+        if let Some(range) = &self.selection {
+            // LSP lines are 0-based
+            prompt.push_str(&format!(" Limit your changes to lines {}-{} of it.", range.start.line + 1, range.end.line + 1));
+        }
+        if let Some(text) = &self.selected_text {
+            prompt.push_str(&format!("\nSelected text:\n```\n{}\n```", text));
+        }
+        prompt
+    }
+}
+
+/// `rumi serve`: runs tasks for an editor over JSON-RPC on stdio.
+///
+/// Editor -> rumi: `initialize`, `rumi/startTask` (answered when the task ends),
+/// `rumi/interrupt`, `$/cancelRequest`, `shutdown`, `exit`.
+/// rumi -> editor: `rumi/progress` notifications, `rumi/approve`,
+/// `rumi/followUp` and `workspace/applyEdit` requests.
+pub async fn serve(config: Arc<Config>) {
+    let transport = Arc::new(Transport {
+        writer: Mutex::new(io::stdout()),
+        next_id: AtomicU64::new(1),
+        pending: Mutex::new(HashMap::new()),
+    });
+
+    // Responses are routed straight to the waiting agent; everything else goes to the loop below
+    let (incoming, mut requests) = tokio::sync::mpsc::unbounded_channel();
+    let reader_transport = transport.clone();
+    std::thread::spawn(move || {
+        let mut stdin = io::stdin().lock();
+        loop {
+            match read_message(&mut stdin) {
+                Ok(Some(message)) if message.get("method").is_some() => {
+                    if incoming.send(message).is_err() {
+                        break;
+                    }
+                }
+                Ok(Some(response)) => reader_transport.resolve(response),
+                Ok(None) => break,
+                Err(e) => {
+                    eprintln!("Invalid message from the editor: {}", e);
+                    break;
+                }
+            }
+        }
+        reader_transport.close();
+    });
+
+    let mut running: HashMap<String, RunningTask> = HashMap::new();
+    while let Some(message) = requests.recv().await {
+        running.retain(|_, running| !running.handle.is_finished());
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = message["params"].clone();
+        let Some(id) = message.get("id").cloned() else {
+            match method {
+                "$/cancelRequest" => {
+                    let key = params["id"].to_string();
+                    if let Some(task) = running.remove(&key) {
+                        task.cancelled.store(true, Ordering::SeqCst);
+                        transport.cancel(&key);
+                        task.handle.abort();
+                        transport.respond_error(task.id, REQUEST_CANCELLED, "Task cancelled");
+                    }
+                }
+                "exit" => break,
+                // Other notifications (initialized, didOpen, ...) carry nothing we need
+                _ => {}
+            }
+            continue;
+        };
+
+        match method {
+            "initialize" => transport.respond(
+                id,
+                json!({
+                    "serverInfo": { "name": "rumi", "version": env!("CARGO_PKG_VERSION") },
+                    "capabilities": { "workspaceEdits": true, "approvals": true, "cancellation": true, "followUp": true },
+                }),
+            ),
+            "rumi/startTask" => match serde_json::from_value::<StartTask>(params) {
+                Ok(params) => {
+                    let reporter = Arc::new(RpcReporter {
+                        transport: transport.clone(),
+                        request: id.clone(),
+                        cancelled: Arc::new(AtomicBool::new(false)),
+                    });
+                    let cancelled = reporter.cancelled.clone();
+                    let interrupt = Interrupt::default();
+                    let handle = tokio::spawn(run_task(config.clone(), transport.clone(), reporter, interrupt.clone(), params));
+                    running.insert(id.to_string(), RunningTask { id, handle, cancelled, interrupt });
+                }
+                Err(e) => transport.respond_error(id, INVALID_PARAMS, &e.to_string()),
+            },
+            // Like Ctrl-C: stops what the task is doing and asks with `rumi/followUp`
+            "rumi/interrupt" => match running.get(&params["request"].to_string()) {
+                Some(task) => {
+                    task.interrupt.press();
+                    transport.respond(id, Value::Null);
+                }
+                None => transport.respond_error(id, INVALID_PARAMS, "No running task with that request id"),
+            },
+            "shutdown" => transport.respond(id, Value::Null),
+            _ => transport.respond_error(id, METHOD_NOT_FOUND, &format!("Unknown method: {}", method)),
+        }
+    }
+}
+
+/// A `rumi/startTask` in progress.
+struct RunningTask {
+    id: Value,
+    handle: JoinHandle<()>,
+    cancelled: Arc<AtomicBool>,
+    interrupt: Interrupt,
+}
+
+async fn run_task(config: Arc<Config>, transport: Arc<Transport>, reporter: Arc<RpcReporter>, interrupt: Interrupt, params: StartTask) {
+    let id = reporter.request.clone();
+    let mut agent = crate::start_agent(&config, &params.prompt(), params.plan, reporter).await.with_interrupt(interrupt);
+    let success = if params.plan { agent.run_planned().await } else { agent.run().await };
+    let totals = agent.finish(success);
+    let session = agent.session.path().display().to_string();
+    transport.respond(id, json!({ "success": success, "session": session, "usage": totals }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_message() {
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"initialize"}"#;
+        let input = format!("Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{}", body.len(), body);
+        let mut reader = io::Cursor::new(input.into_bytes());
+        let message = read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(message["method"], "initialize");
+        assert!(read_message(&mut reader).unwrap().is_none());
+
+        let mut headless = io::Cursor::new(b"Content-Type: x\r\n\r\n{}".to_vec());
+        assert!(read_message(&mut headless).is_err());
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("/src/My%20File.res"), "/src/My File.res");
+        assert_eq!(percent_decode("/%C3%BCber.rs"), "/über.rs");
+        // Not an escape: kept as is
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+
+    #[test]
+    fn test_uri_round_trip() {
+        let path = env::temp_dir().join("rumi rpc").join("My File#1%.res");
+        let path = path.to_string_lossy().into_owned();
+        let uri = path_to_uri(&path);
+        assert!(uri.starts_with("file:///") && uri.ends_with("/rumi%20rpc/My%20File%231%25.res"), "{}", uri);
+        assert_eq!(uri_to_path(&uri), path);
+    }
+
+    #[test]
+    fn test_workspace_edit() {
+        let dir = env::temp_dir().join(format!("rumi-rpc-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let existing = dir.join("a.txt");
+        fs::write(&existing, "one\ntwo €𝄞").unwrap();
+        let existing = existing.to_string_lossy().into_owned();
+
+        let edit = workspace_edit(&existing, "new").unwrap();
+        let change = &edit["changes"][path_to_uri(&existing)][0];
+        // `two €` is 5 UTF-16 units and 𝄞 a surrogate pair
+        assert_eq!(change["range"]["end"], json!({ "line": 1, "character": 7 }));
+        assert_eq!(change["newText"], "new");
+
+        let missing = dir.join("b.txt").to_string_lossy().into_owned();
+        let edit = workspace_edit(&missing, "new").unwrap();
+        assert_eq!(edit["documentChanges"][0], json!({ "kind": "create", "uri": path_to_uri(&missing) }));
+
+        // A directory cannot be read, and must not be created over
+        assert!(workspace_edit(&dir.to_string_lossy(), "new").is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
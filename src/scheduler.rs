@@ -0,0 +1,172 @@
+use crate::agent::Agent;
+use crate::events::AgentEvent;
+use crate::tools;
+use crate::usage::UsageTotals;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+
+/// File-level locks shared by all sub-agents of one run, so that two agents
+/// never edit the same path at the same time.
+#[derive(Clone, Default)]
+pub struct FileLocks {
+    held: Arc<Mutex<HashMap<PathBuf, usize>>>,
+}
+
+impl FileLocks {
+    /// Claims `path` for `owner`. Fails with the current holder if another agent has it.
+    pub fn claim(&self, path: &str, owner: usize) -> Result<(), usize> {
+        let mut held = self.held.lock().unwrap_or_else(|e| e.into_inner());
+        let holder = *held.entry(tools::normalize(Path::new(path))).or_insert(owner);
+        if holder == owner { Ok(()) } else { Err(holder) }
+    }
+
+    pub fn release_all(&self, owner: usize) {
+        let mut held = self.held.lock().unwrap_or_else(|e| e.into_inner());
+        held.retain(|_, holder| *holder != owner);
+    }
+}
+
+/// One independent piece of work for a sub-agent, scoped to a single file.
+pub struct WorkUnit {
+    pub id: usize,
+    pub file: String,
+    pub agent: Agent,
+}
+
+pub struct UnitReport {
+    pub id: usize,
+    pub file: String,
+    pub success: bool,
+    pub session_path: PathBuf,
+    pub totals: UsageTotals,
+}
+
+impl UnitReport {
+    pub fn tokens(&self) -> u64 {
+        self.totals.prompt_tokens + self.totals.completion_tokens
+    }
+}
+
+/// Runs up to `jobs` sub-agents at once. Each unit holds the lock on its own
+/// file for its whole run; files it creates are locked on first write.
+pub async fn run_parallel(units: Vec<WorkUnit>, jobs: usize, locks: FileLocks, plan: bool) -> Vec<UnitReport> {
+    let tasks = units.into_iter().map(|unit| {
+        let locks = locks.clone();
+        async move {
+            let WorkUnit { id, file, mut agent } = unit;
+            let success = match locks.claim(&file, id) {
+                Ok(()) => {
+                    agent.report(AgentEvent::Info { text: format!("\n=== Unit {} started: {} ===", id, file) });
+                    if plan { agent.run_planned().await } else { agent.run().await }
+                }
+                Err(holder) => {
+                    let text = format!("\n=== Unit {} skipped: {} is locked by unit {} ===", id, file, holder);
+                    agent.report(AgentEvent::Info { text });
+                    false
+                }
+            };
+            locks.release_all(id);
+            let status = if success { "finished" } else { "failed" };
+            agent.report(AgentEvent::Info { text: format!("\n=== Unit {} {}: {} ===", id, status, file) });
+            let totals = agent.export_usage();
+            UnitReport { id, file, success, session_path: agent.session.path(), totals }
+        }
+    });
+
+    let mut reports = limited(tasks.collect(), jobs).await;
+    reports.sort_by_key(|r| r.id);
+    reports
+}
+
+/// Spawns every task but lets at most `jobs` run at once. Returns the outputs
+/// in task order; a task that panicked is reported and left out.
+async fn limited<T, F>(tasks: Vec<F>, jobs: usize) -> Vec<T>
+where
+    T: Send + 'static,
+    F: Future<Output = T> + Send + 'static,
+{
+    let permits = Arc::new(Semaphore::new(jobs.max(1)));
+    let handles: Vec<_> = tasks
+        .into_iter()
+        .map(|task| {
+            let permits = permits.clone();
+            tokio::spawn(async move {
+                // The semaphore is never closed, so this always gets a permit
+                let _permit = permits.acquire_owned().await;
+                task.await
+            })
+        })
+        .collect();
+
+    let mut outputs = Vec::new();
+    for handle in handles {
+        match handle.await {
+            Ok(output) => outputs.push(output),
+            Err(e) => eprintln!("Sub-agent crashed: {}", e),
+        }
+    }
+    outputs
+}
+
+/// Collects the results of all units into one markdown report.
+pub fn render_report(title: &str, reports: &[UnitReport]) -> String {
+    let done = reports.iter().filter(|r| r.success).count();
+    let tokens: u64 = reports.iter().map(UnitReport::tokens).sum();
+    let mut out = format!("# {}\n\n{}/{} units finished, {} tokens used.\n\n", title, done, reports.len(), tokens);
+    for report in reports {
+        out.push_str(&format!(
+            "- [{}] unit {}: {} ({} tokens, session: {})\n",
+            if report.success { "x" } else { " " },
+            report.id,
+            report.file,
+            report.tokens(),
+            report.session_path.display()
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn test_locks_conflict_on_the_same_file() {
+        let locks = FileLocks::default();
+        assert_eq!(locks.claim("src/a.rs", 1), Ok(()));
+        assert_eq!(locks.claim("./src/a.rs", 1), Ok(()));
+        assert_eq!(locks.claim("./src/./a.rs", 2), Err(1));
+        assert_eq!(locks.claim("src/lib/../a.rs", 2), Err(1));
+        assert_eq!(locks.claim("src/b.rs", 2), Ok(()));
+
+        locks.release_all(1);
+        assert_eq!(locks.claim("src/a.rs", 2), Ok(()));
+        assert_eq!(locks.claim("src/a.rs", 3), Err(2));
+    }
+
+    #[tokio::test]
+    async fn test_at_most_jobs_tasks_run_at_once() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let tasks: Vec<_> = (0..8)
+            .map(|i| {
+                let (running, most) = (running.clone(), most.clone());
+                async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    most.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    i
+                }
+            })
+            .collect();
+
+        assert_eq!(limited(tasks, 3).await, (0..8).collect::<Vec<_>>());
+        assert_eq!(most.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::git::Worktree;
+use crate::llm::Message;
+use crate::plan::Plan;
+use crate::usage::UsageLog;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::BuildHasher;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SESSIONS_DIR: &str = ".rumi/sessions";
+
+/// State of one agent run, persisted under `.rumi/sessions/<id>.json`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Session {
+    pub id: String,
+    pub task: String,
+    pub started_at: u64,
+    #[serde(default)]
+    pub plan: Plan,
+    #[serde(default)]
+    pub usage: UsageLog,
+    /// The conversation since the last compaction, resent with every request.
+    #[serde(default)]
+    pub history: Vec<Message>,
+    /// What compaction kept of the older turns (see `compaction`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    /// Set while the session works in its own worktree (`[git] isolate`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub worktree: Option<Worktree>,
+}
+
+impl Session {
+    pub fn new(task: &str) -> Self {
+        let started_at = now();
+        Session {
+            id: unique_id(started_at),
+            task: task.to_string(),
+            started_at,
+            plan: Plan::default(),
+            usage: UsageLog::default(),
+            history: Vec::new(),
+            state: None,
+            worktree: None,
+        }
+    }
+
+    pub fn load(id: &str) -> std::io::Result<Self> {
+        let json = fs::read_to_string(PathBuf::from(SESSIONS_DIR).join(format!("{}.json", id)))?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// A sub-agent session, named after the run that spawned it.
+    pub fn child(&self, unit: usize, task: &str) -> Self {
+        Session {
+            id: format!("{}-{}", self.id, unit),
+            task: task.to_string(),
+            started_at: now(),
+            plan: Plan::default(),
+            usage: UsageLog::default(),
+            history: Vec::new(),
+            state: None,
+            // Sub-agents share the parent's worktree, which the parent owns
+            worktree: None,
+        }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        PathBuf::from(SESSIONS_DIR).join(format!("{}.json", self.id))
+    }
+
+    pub fn save(&self) -> std::io::Result<PathBuf> {
+        let path = self.path();
+        fs::create_dir_all(SESSIONS_DIR)?;
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(&path, json)?;
+        Ok(path)
+    }
+}
+
+/// The start time with a random suffix, so sessions started in the same
+/// second (parallel `rumi/startTask` requests, two `tasks run`) get their own
+/// session file and worktree branch.
+fn unique_id(started_at: u64) -> String {
+    loop {
+        let suffix = RandomState::new().hash_one(started_at) & 0xff_ffff;
+        let id = format!("{}-{:06x}", started_at, suffix);
+        if !PathBuf::from(SESSIONS_DIR).join(format!("{}.json", id)).exists() {
+            return id;
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions_started_together_get_distinct_ids() {
+        let first = Session::new("a");
+        let second = Session::new("b");
+        assert_ne!(first.id, second.id);
+        assert!(first.id.starts_with(&format!("{}-", first.started_at)));
+        assert_eq!(first.child(1, "c").id, format!("{}-1", first.id));
+    }
+}
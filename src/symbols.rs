@@ -0,0 +1,54 @@
+use efficiency_analyzer::drivers::Symbol;
+use efficiency_analyzer::symbols::{file_symbols, find_definitions, find_references};
+use std::path::Path;
+
+/// Most references listed before the rest are only counted.
+const MAX_REFERENCES: usize = 100;
+
+fn describe(symbol: &Symbol) -> String {
+    format!("L{}-{} {}: {}", symbol.start_line, symbol.end_line, symbol.name, symbol.signature)
+}
+
+/// `path` relative to `root`, as the model wrote it.
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root).unwrap_or(path).display().to_string()
+}
+
+/// One line per symbol in `path`: line range, name and signature.
+pub fn list(root: &Path, path: &str) -> (String, bool) {
+    match file_symbols(&root.join(path)) {
+        Ok(symbols) if symbols.is_empty() => (format!("No symbols found in {} (only Rust and ReScript are parsed).", path), true),
+        Ok(symbols) => (symbols.iter().map(describe).collect::<Vec<_>>().join("\n"), true),
+        Err(e) => (format!("Failed to parse {}: {}", path, e), false),
+    }
+}
+
+/// Where `name` (or `Type::name`) is defined, as `path:L<start>-<end>`.
+pub fn definition(root: &Path, name: &str) -> (String, bool) {
+    let found = find_definitions(root, name);
+    if found.is_empty() {
+        return (format!("No definition of {} found.", name), false);
+    }
+    let lines: Vec<String> = found.iter().map(|(path, symbol)| format!("{}:{}", relative(root, path), describe(symbol))).collect();
+    (lines.join("\n"), true)
+}
+
+/// Every line mentioning `name` as a whole word, as `path:L<line>: <text>`.
+pub fn references(root: &Path, name: &str) -> (String, bool) {
+    let found = match find_references(root, name) {
+        Ok(found) => found,
+        Err(e) => return (format!("Cannot search for {:?}: {}", name, e), false),
+    };
+    if found.is_empty() {
+        return (format!("No references to {} found.", name), true);
+    }
+    let mut out: Vec<String> = found
+        .iter()
+        .take(MAX_REFERENCES)
+        .map(|r| format!("{}:L{}: {}", relative(root, &r.path), r.line, r.text))
+        .collect();
+    if found.len() > MAX_REFERENCES {
+        out.push(format!("... ({} more)", found.len() - MAX_REFERENCES));
+    }
+    (out.join("\n"), true)
+}
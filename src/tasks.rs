@@ -0,0 +1,259 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where the analyzer writes its tasks, relative to the project root.
+const TASKS_DIR: &str = "tasks";
+/// The analyzer state file, relative to the project root.
+pub const ANALYZER_STATE: &str = "_dev-system/analyzer_state.json";
+/// The analyzer's thresholds and complexity dictionaries, relative to the project root.
+pub const ANALYZER_CONFIG: &str = "_dev-system/config/efficiency.json";
+/// The analyzer writes paths relative to `_dev-system/analyzer`.
+pub const ANALYZER_PREFIX: &str = "../../";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskStatus {
+    Pending,
+    Active,
+    Completed,
+    Postponed,
+}
+
+impl TaskStatus {
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            TaskStatus::Pending => "pending",
+            TaskStatus::Active => "active",
+            TaskStatus::Completed => "completed",
+            TaskStatus::Postponed => "postponed",
+        }
+    }
+}
+
+/// A file or symbol the task asks the agent to work on.
+#[derive(Debug, Clone)]
+pub struct TaskTarget {
+    /// Path as written by the analyzer (e.g. `../../src/core/Reducer.res`).
+    pub analyzer_path: String,
+    /// Semantic hotspot (`Function: foo`) or line range, when the analyzer found one.
+    pub hotspot: Option<String>,
+    /// The full task list line, with the analyzer's metrics.
+    pub detail: String,
+}
+
+impl TaskTarget {
+    /// Path relative to the project root, which is where rumi runs.
+    pub fn path(&self) -> &str {
+        self.analyzer_path.strip_prefix(ANALYZER_PREFIX).unwrap_or(&self.analyzer_path)
+    }
+}
+
+/// A `tasks/<status>/NNN_Name.md` file produced by the `_dev-system` analyzer.
+#[derive(Debug, Clone)]
+pub struct TaskFile {
+    pub id: String,
+    pub title: String,
+    pub status: TaskStatus,
+    pub path: PathBuf,
+    pub objective: String,
+    pub body: String,
+    pub targets: Vec<TaskTarget>,
+}
+
+impl TaskFile {
+    pub fn load(path: &Path, status: TaskStatus) -> Option<Self> {
+        let file_name = path.file_name()?.to_string_lossy().into_owned();
+        let id = file_name.split('_').next()?.to_string();
+        id.parse::<usize>().ok()?;
+        let content = fs::read_to_string(path).ok()?;
+
+        let title = content
+            .lines()
+            .find_map(|l| l.strip_prefix("# "))
+            .map(|l| l.split_once(": ").map(|(_, t)| t).unwrap_or(l).trim().to_string())
+            .unwrap_or_else(|| file_name.trim_end_matches(".md").to_string());
+
+        let (objective, body) = section(&content, "## Objective");
+        Some(TaskFile {
+            id,
+            title,
+            status,
+            path: path.to_path_buf(),
+            objective,
+            body,
+            targets: parse_targets(&content),
+        })
+    }
+
+    /// Moves the file to another status folder, e.g. `pending` -> `active`.
+    pub fn move_to(&mut self, status: TaskStatus) -> std::io::Result<()> {
+        let dir = Path::new(TASKS_DIR).join(status.dir_name());
+        fs::create_dir_all(&dir)?;
+        let target = dir.join(self.path.file_name().unwrap_or_default());
+        fs::rename(&self.path, &target)?;
+        self.path = target;
+        self.status = status;
+        Ok(())
+    }
+
+    /// The instruction given to the agent for this task.
+    pub fn to_prompt(&self) -> String {
+        let mut out = format!("Work on analyzer task {}: {}\n\n# OBJECTIVE\n{}\n", self.id, self.title, self.objective);
+        if !self.targets.is_empty() {
+            out.push_str("\n# TARGETS\n");
+            for target in &self.targets {
+                match &target.hotspot {
+                    Some(hotspot) => out.push_str(&format!("- {} (hotspot: {})\n", target.path(), hotspot)),
+                    None => out.push_str(&format!("- {}\n", target.path())),
+                }
+            }
+        }
+        if !self.body.is_empty() {
+            out.push_str(&format!("\n# DETAILS\n{}\n", self.body));
+        }
+        out
+    }
+
+    /// The instruction for a sub-agent that only handles one of the targets.
+    pub fn unit_prompt(&self, target: &TaskTarget) -> String {
+        let mut out = format!("Work on one target of analyzer task {}: {}\n\n# OBJECTIVE\n{}\n", self.id, self.title, self.objective);
+        out.push_str(&format!("\n# TARGET\n{}\n", target.path()));
+        if let Some(hotspot) = &target.hotspot {
+            out.push_str(&format!("Hotspot: {}\n", hotspot));
+        }
+        out.push_str(&format!("Analyzer metrics: {}\n", target.detail));
+        out.push_str("\nOther agents are working on the other targets at the same time. Only edit this file and new files you create for it, with write_file. Shell commands need the user's approval, and the user commits once the whole task is done.\n");
+        out
+    }
+}
+
+/// Lists every task in the given status folders, sorted by id.
+pub fn list(statuses: &[TaskStatus]) -> Vec<TaskFile> {
+    let mut tasks = Vec::new();
+    for status in statuses {
+        let dir = Path::new(TASKS_DIR).join(status.dir_name());
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("md") {
+                continue;
+            }
+            if let Some(task) = TaskFile::load(&path, *status) {
+                tasks.push(task);
+            }
+        }
+    }
+    tasks.sort_by_key(|t| t.id.parse::<usize>().unwrap_or(usize::MAX));
+    tasks
+}
+
+/// Finds a runnable task by id; `7`, `007` and `007_Name` all match. Active
+/// tasks are runnable too, since a run that exits early leaves its task there.
+pub fn find(id: &str) -> Option<TaskFile> {
+    let wanted = id.split('_').next()?.parse::<usize>().ok()?;
+    list(&[TaskStatus::Pending, TaskStatus::Active, TaskStatus::Postponed])
+        .into_iter()
+        .find(|t| t.id.parse::<usize>().ok() == Some(wanted))
+}
+
+/// Splits out the text under `header` (up to the next `## ` header) and everything after it.
+/// The objective templates open with their own `## ...` title, so a header directly
+/// under `header` belongs to the section.
+fn section(content: &str, header: &str) -> (String, String) {
+    let mut lines = content.lines().skip_while(|l| l.trim() != header);
+    if lines.next().is_none() {
+        return (String::new(), String::new());
+    }
+    let mut inside = Vec::new();
+    let mut rest = Vec::new();
+    for line in lines {
+        let has_content = inside.iter().any(|l: &&str| !l.trim().is_empty());
+        if rest.is_empty() && !(line.starts_with("## ") && has_content) {
+            inside.push(line);
+        } else {
+            rest.push(line);
+        }
+    }
+    (inside.join("\n").trim().to_string(), rest.join("\n").trim().to_string())
+}
+
+/// Extracts `**path**` / `` `path` `` entries from the unchecked task list items
+/// (and the nested file lists of merge items).
+fn parse_targets(content: &str) -> Vec<TaskTarget> {
+    let mut targets: Vec<TaskTarget> = Vec::new();
+    for line in content.lines() {
+        let line = line.trim_start();
+        let Some(item) = line
+            .strip_prefix("- [ ]")
+            .or_else(|| line.strip_prefix("- ").filter(|i| i.starts_with('`')))
+        else {
+            continue;
+        };
+        let Some(path) = delimited(item, "**").or_else(|| delimited(item, "`")) else {
+            continue;
+        };
+        // Folders of merge items have no extension; their files follow as nested items
+        if Path::new(path).extension().is_none() || targets.iter().any(|t| t.analyzer_path == path) {
+            continue;
+        }
+        let hotspot = if let Some((_, rest)) = item.split_once("🎯 Target: ") {
+            Some(rest.split(" (").next().unwrap_or(rest).trim().to_string())
+        } else {
+            item.split_once("Hotspot: ")
+                .map(|(_, rest)| rest.split(" (").next().unwrap_or(rest).trim().to_string())
+        };
+        targets.push(TaskTarget {
+            analyzer_path: path.to_string(),
+            hotspot,
+            detail: item.trim().trim_start_matches("- ").to_string(),
+        });
+    }
+    targets
+}
+
+fn delimited<'a>(text: &'a str, marker: &str) -> Option<&'a str> {
+    let start = text.find(marker)? + marker.len();
+    let end = text[start..].find(marker)? + start;
+    Some(&text[start..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TASK: &str = "# Task 007: Surgical Refactor Frontend
+
+## Objective
+## Surgical Refactor
+Split the hotspots out of the flagged modules.
+
+## Tasks
+- [ ] **../../src/core/Reducer.res** (Metric: [Nesting: 1.20, Density: 0.40, Coupling: 0.10] | Drag: 3.10 | LOC: 420/300  🎯 Target: Function: reduce (High complexity))
+- [ ] **../../src/core/View.res** (Metric: [Nesting: 0.80] | Drag: 2.00 | LOC: 350/300  Hotspot: Lines 10-80 (Deep nesting))
+- [x] **../../src/core/Done.res** (Metric: done)
+- [ ] **../../src/core/Reducer.res** (Metric: repeated)
+- [ ] Folder: `../../src/utils` (Metric: 12 small files)
+    - `../../src/utils/Strings.res`
+    - `../../src/utils/Arrays.res`
+";
+
+    #[test]
+    fn test_parse_targets() {
+        let targets = parse_targets(TASK);
+        let paths: Vec<&str> = targets.iter().map(|t| t.path()).collect();
+        assert_eq!(paths, vec!["src/core/Reducer.res", "src/core/View.res", "src/utils/Strings.res", "src/utils/Arrays.res"]);
+
+        assert_eq!(targets[0].hotspot.as_deref(), Some("Function: reduce"));
+        assert_eq!(targets[1].hotspot.as_deref(), Some("Lines 10-80"));
+        assert_eq!(targets[2].hotspot, None);
+        assert!(targets[0].detail.starts_with("**../../src/core/Reducer.res** (Metric: [Nesting: 1.20"));
+    }
+
+    #[test]
+    fn test_section_keeps_the_template_title() {
+        let (objective, rest) = section(TASK, "## Objective");
+        assert_eq!(objective, "## Surgical Refactor\nSplit the hotspots out of the flagged modules.");
+        assert!(rest.starts_with("## Tasks\n- [ ] **../../src/core/Reducer.res**"));
+    }
+}
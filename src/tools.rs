@@ -1,6 +1,20 @@
+use crate::analysis::analyze_file;
+use crate::config::Config;
+use crate::custom_tools::CustomTool;
+use crate::diagnostics::check_project;
+use crate::docs;
+use crate::git;
+use crate::llm::Image;
+use crate::mcp::McpTool;
+use crate::memory::{MemoryKind, MemoryStore};
+use crate::plan::Plan;
+use crate::process;
+use crate::symbols;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::process::Command;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use tokio::process::Command;
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "tool", content = "args")]
@@ -11,6 +25,230 @@ pub enum ToolCall {
     WriteFile { path: String, content: String },
     #[serde(rename = "run_shell")]
     RunShell { command: String },
+    #[serde(rename = "check")]
+    Check {
+        #[serde(default)]
+        path: Option<String>,
+    },
+    #[serde(rename = "remember")]
+    Remember {
+        text: String,
+        #[serde(default)]
+        kind: MemoryKind,
+        #[serde(default)]
+        tags: Vec<String>,
+        #[serde(default)]
+        files: Vec<String>,
+    },
+    #[serde(rename = "git_status")]
+    GitStatus {},
+    #[serde(rename = "git_diff")]
+    GitDiff {
+        #[serde(default)]
+        path: Option<String>,
+        #[serde(default)]
+        staged: bool,
+    },
+    #[serde(rename = "git_log")]
+    GitLog {
+        #[serde(default)]
+        path: Option<String>,
+        #[serde(default = "default_log_limit")]
+        limit: usize,
+    },
+    #[serde(rename = "git_blame")]
+    GitBlame { path: String, start: usize, end: usize },
+    /// `message` is expanded through `[git] commit_template` before approval.
+    #[serde(rename = "git_commit")]
+    GitCommit {
+        message: String,
+        #[serde(default)]
+        files: Vec<String>,
+    },
+    #[serde(rename = "list_symbols")]
+    ListSymbols { path: String },
+    #[serde(rename = "find_definition")]
+    FindDefinition { name: String },
+    #[serde(rename = "find_references")]
+    FindReferences { name: String },
+    #[serde(rename = "analyze_file")]
+    AnalyzeFile { path: String },
+    #[serde(rename = "view_image")]
+    ViewImage { path: String },
+    #[serde(rename = "docs_lookup")]
+    DocsLookup {
+        query: String,
+        #[serde(default = "default_docs_limit")]
+        limit: usize,
+    },
+    /// Only offered with `[docs] fetch = true`.
+    #[serde(rename = "fetch_url")]
+    FetchUrl {
+        url: String,
+        #[serde(default)]
+        refresh: bool,
+    },
+    /// A `[[tools.custom]]` tool; built by `Agent` from the config, never parsed directly.
+    #[serde(skip)]
+    Custom { tool: CustomTool, args: serde_json::Value },
+    /// A tool of a connected MCP server, called as `server.tool`.
+    #[serde(skip)]
+    Mcp { tool: McpTool, args: serde_json::Value },
+    #[serde(rename = "update_plan")]
+    UpdatePlan { steps: Vec<String> },
+    #[serde(rename = "complete_step")]
+    CompleteStep {
+        step: usize,
+        #[serde(default)]
+        note: Option<String>,
+    },
+    /// Ends the run; the agent verifies the work first (see `verify`).
+    #[serde(rename = "finish")]
+    Finish { summary: String },
+}
+
+fn default_log_limit() -> usize {
+    10
+}
+
+fn default_docs_limit() -> usize {
+    3
+}
+
+impl ToolCall {
+    pub fn name(&self) -> &str {
+        match self {
+            ToolCall::ReadFile { .. } => "read_file",
+            ToolCall::WriteFile { .. } => "write_file",
+            ToolCall::RunShell { .. } => "run_shell",
+            ToolCall::Check { .. } => "check",
+            ToolCall::Remember { .. } => "remember",
+            ToolCall::GitStatus { .. } => "git_status",
+            ToolCall::GitDiff { .. } => "git_diff",
+            ToolCall::GitLog { .. } => "git_log",
+            ToolCall::GitBlame { .. } => "git_blame",
+            ToolCall::GitCommit { .. } => "git_commit",
+            ToolCall::ListSymbols { .. } => "list_symbols",
+            ToolCall::FindDefinition { .. } => "find_definition",
+            ToolCall::FindReferences { .. } => "find_references",
+            ToolCall::AnalyzeFile { .. } => "analyze_file",
+            ToolCall::ViewImage { .. } => "view_image",
+            ToolCall::DocsLookup { .. } => "docs_lookup",
+            ToolCall::FetchUrl { .. } => "fetch_url",
+            ToolCall::Custom { tool, .. } => &tool.name,
+            ToolCall::Mcp { tool, .. } => &tool.name,
+            ToolCall::UpdatePlan { .. } => "update_plan",
+            ToolCall::CompleteStep { .. } => "complete_step",
+            ToolCall::Finish { .. } => "finish",
+        }
+    }
+
+    /// One example call of every built-in tool, in the order the prompt lists
+    /// them. The prompt's tool docs are generated from these, so the names and
+    /// arguments shown are always the ones `ToolCall` parses.
+    pub fn examples() -> Vec<ToolCall> {
+        let path = || "path/to/file".to_string();
+        vec![
+            ToolCall::ReadFile { path: path() },
+            ToolCall::WriteFile { path: path(), content: "...".to_string() },
+            ToolCall::RunShell { command: "ls src".to_string() },
+            ToolCall::Check { path: Some(".".to_string()) },
+            ToolCall::Remember {
+                text: "...".to_string(),
+                kind: MemoryKind::default(),
+                tags: vec!["...".to_string()],
+                files: vec![path()],
+            },
+            ToolCall::GitStatus {},
+            ToolCall::GitDiff { path: Some("optional/path".to_string()), staged: false },
+            ToolCall::GitLog { path: Some("optional/path".to_string()), limit: default_log_limit() },
+            ToolCall::GitBlame { path: path(), start: 10, end: 20 },
+            ToolCall::GitCommit { message: "Short summary of the change".to_string(), files: vec![path()] },
+            ToolCall::ListSymbols { path: path() },
+            ToolCall::FindDefinition { name: "Type::method or function".to_string() },
+            ToolCall::FindReferences { name: "function".to_string() },
+            ToolCall::AnalyzeFile { path: path() },
+            ToolCall::ViewImage { path: "path/to/screenshot.png".to_string() },
+            ToolCall::DocsLookup { query: "serde rename".to_string(), limit: default_docs_limit() },
+            ToolCall::FetchUrl { url: "https://docs.rs/serde/latest/serde/".to_string(), refresh: false },
+            ToolCall::UpdatePlan { steps: vec!["first step".to_string(), "second step".to_string()] },
+            ToolCall::CompleteStep { step: 1, note: Some("optional short result".to_string()) },
+            ToolCall::Finish { summary: "What was changed and how it was checked".to_string() },
+        ]
+    }
+
+    /// What the tool does, shown under its example in the prompt.
+    pub fn description(&self) -> &'static str {
+        match self {
+            ToolCall::ReadFile { .. } => "Returns the content of a file.",
+            ToolCall::WriteFile { .. } => "Replaces the whole content of a file.",
+            ToolCall::RunShell { .. } => "Runs a shell command in the project and returns its output.",
+            ToolCall::Check { .. } => "Compiles the project (cargo check or the ReScript build) and returns the errors.",
+            ToolCall::Remember { .. } => "Saves a fact, convention or mistake for later sessions (kind: fact, convention or mistake).",
+            ToolCall::GitStatus { .. } => "Shows the branch and the changed files.",
+            ToolCall::GitDiff { .. } => "Shows the uncommitted changes, or the staged ones.",
+            ToolCall::GitLog { .. } => "Shows the latest commits.",
+            ToolCall::GitBlame { .. } => "Shows who last changed each line of a range.",
+            ToolCall::GitCommit { .. } => "Commits the files (or what is staged) once the user approves.",
+            ToolCall::ListSymbols { .. } => "Lists the functions, types and impls of a file with their lines.",
+            ToolCall::FindDefinition { .. } => "Finds where a symbol is defined.",
+            ToolCall::FindReferences { .. } => "Finds where a symbol is used.",
+            ToolCall::AnalyzeFile { .. } => "Runs the efficiency analyzer on a file and returns its drag and hotspots.",
+            ToolCall::ViewImage { .. } => "Attaches an image to the next message.",
+            ToolCall::DocsLookup { .. } => "Searches the project's markdown references and the pages fetched before.",
+            ToolCall::FetchUrl { .. } => "Fetches a web page as text and caches it for docs_lookup.",
+            ToolCall::UpdatePlan { .. } => "Replaces the plan with these steps.",
+            ToolCall::CompleteStep { .. } => "Marks a plan step as done.",
+            ToolCall::Finish { .. } => "Ends the task with a summary of the work; it may be checked first.",
+            // Described by their own prompt entries
+            ToolCall::Custom { .. } | ToolCall::Mcp { .. } => "",
+        }
+    }
+
+    /// The example call as JSON with its description, for the prompt.
+    pub fn prompt_entry(&self) -> String {
+        format!("{{ \"tool\": \"{}\", \"args\": {} }}\n  {}", self.name(), self.args(), self.description())
+    }
+
+    /// The call's arguments as JSON, for machine-readable output.
+    pub fn args(&self) -> serde_json::Value {
+        if let ToolCall::Custom { args, .. } | ToolCall::Mcp { args, .. } = self {
+            return args.clone();
+        }
+        serde_json::to_value(self).map(|v| v["args"].clone()).unwrap_or_default()
+    }
+
+    /// One line describing the call, shown when asking for approval.
+    pub fn summary(&self) -> String {
+        match self {
+            ToolCall::ReadFile { path } => format!("read_file {}", path),
+            ToolCall::WriteFile { path, content } => format!("write_file {} ({} bytes)", path, content.len()),
+            ToolCall::RunShell { command } => format!("run_shell: {}", command),
+            ToolCall::Check { path } => format!("check {}", path.as_deref().unwrap_or(".")),
+            ToolCall::Remember { text, .. } => format!("remember: {}", text),
+            ToolCall::GitStatus {} => "git_status".to_string(),
+            ToolCall::GitDiff { path, .. } => format!("git_diff {}", path.as_deref().unwrap_or(".")),
+            ToolCall::GitLog { path, limit } => format!("git_log {} (last {})", path.as_deref().unwrap_or("."), limit),
+            ToolCall::GitBlame { path, start, end } => format!("git_blame {}:{}-{}", path, start, end),
+            ToolCall::GitCommit { message, files } if files.is_empty() => format!("git_commit staged files:\n{}", message),
+            ToolCall::GitCommit { message, files } => format!("git_commit {}:\n{}", files.join(", "), message),
+            ToolCall::ListSymbols { path } => format!("list_symbols {}", path),
+            ToolCall::FindDefinition { name } => format!("find_definition {}", name),
+            ToolCall::FindReferences { name } => format!("find_references {}", name),
+            ToolCall::AnalyzeFile { path } => format!("analyze_file {}", path),
+            ToolCall::ViewImage { path } => format!("view_image {}", path),
+            ToolCall::DocsLookup { query, .. } => format!("docs_lookup {}", query),
+            ToolCall::FetchUrl { url, .. } => format!("fetch_url {}", url),
+            ToolCall::Custom { tool, args } => match tool.command(args) {
+                Ok(command) => format!("{}: {}", tool.name, command),
+                Err(_) => format!("{} {}", tool.name, args),
+            },
+            ToolCall::Mcp { tool, args } => format!("{} {}", tool.name, args),
+            ToolCall::UpdatePlan { steps } => format!("update_plan ({} steps)", steps.len()),
+            ToolCall::CompleteStep { step, .. } => format!("complete_step {}", step),
+            ToolCall::Finish { summary } => format!("finish: {}", summary),
+        }
+    }
 }
 
 pub struct ToolResult {
@@ -19,10 +257,34 @@ pub struct ToolResult {
     pub success: bool,
 }
 
-pub fn execute_tool(call: ToolCall) -> ToolResult {
+/// Runs a tool call. Paths are relative to `workdir`, which is the session's
+/// worktree when it is isolated. `view_image` adds to `images`, which go out
+/// with the next request. Dropping the future cancels the call: child
+/// processes are killed and a `write_file` in progress leaves the file as it was.
+pub async fn execute_tool(call: ToolCall, plan: &mut Plan, images: &mut Vec<Image>, workdir: &Path, config: &Config) -> ToolResult {
+    // Files the tools open themselves stay inside `workdir`; shell commands are not confined
+    let path = match &call {
+        ToolCall::ReadFile { path }
+        | ToolCall::WriteFile { path, .. }
+        | ToolCall::ViewImage { path }
+        | ToolCall::ListSymbols { path }
+        | ToolCall::AnalyzeFile { path } => Some(path.as_str()),
+        ToolCall::Check { path } => path.as_deref(),
+        _ => None,
+    };
+    if let Some(path) = path
+        && let Err(e) = inside(workdir, path)
+    {
+        return ToolResult {
+            tool_name: call.name().to_string(),
+            output: e,
+            success: false,
+        };
+    }
+
     match call {
         ToolCall::ReadFile { path } => {
-            match fs::read_to_string(&path) {
+            match fs::read_to_string(workdir.join(&path)) {
                 Ok(content) => ToolResult {
                     tool_name: "read_file".to_string(),
                     output: content,
@@ -36,7 +298,7 @@ pub fn execute_tool(call: ToolCall) -> ToolResult {
             }
         }
         ToolCall::WriteFile { path, content } => {
-            match fs::write(&path, content) {
+            match write_file(&workdir.join(&path), &content).await {
                 Ok(_) => ToolResult {
                     tool_name: "write_file".to_string(),
                     output: format!("Successfully wrote to {}", path),
@@ -49,30 +311,272 @@ pub fn execute_tool(call: ToolCall) -> ToolResult {
                 },
             }
         }
-        ToolCall::RunShell { command } => {
-            let output = if cfg!(target_os = "windows") {
-                Command::new("cmd").args(["/C", &command]).output()
-            } else {
-                Command::new("sh").args(["-c", &command]).output()
-            };
-
-            match output {
-                Ok(out) => {
-                    let stdout = String::from_utf8_lossy(&out.stdout);
-                    let stderr = String::from_utf8_lossy(&out.stderr);
-                    let combined = format!("{}{}", stdout, stderr);
-                    ToolResult {
-                        tool_name: "run_shell".to_string(),
-                        output: if combined.is_empty() { "Success (no output)".to_string() } else { combined.to_string() },
-                        success: out.status.success(),
-                    }
-                }
+        ToolCall::RunShell { command } => run_command("run_shell", &command, workdir).await,
+        ToolCall::Custom { tool, args } => match tool.command(&args) {
+            Ok(command) => run_command(&tool.name, &command, workdir).await,
+            Err(e) => ToolResult {
+                tool_name: tool.name,
+                output: e,
+                success: false,
+            },
+        },
+        ToolCall::Mcp { tool, args } => {
+            let result = tool.call(args, images).await;
+            output_result(&tool.name, result)
+        }
+        ToolCall::Check { path } => {
+            let (output, success) = check_project(&workdir.join(path.as_deref().unwrap_or("."))).await;
+            ToolResult {
+                tool_name: "check".to_string(),
+                output,
+                success,
+            }
+        }
+        ToolCall::Remember { text, kind, tags, files } => {
+            match MemoryStore::remember(workdir, kind, text, tags, files) {
+                Ok(id) => ToolResult {
+                    tool_name: "remember".to_string(),
+                    output: format!("Saved memory #{}", id),
+                    success: true,
+                },
                 Err(e) => ToolResult {
-                    tool_name: "run_shell".to_string(),
-                    output: format!("Failed to execute command: {}", e),
+                    tool_name: "remember".to_string(),
+                    output: format!("Error saving memory: {}", e),
                     success: false,
                 },
             }
         }
+        ToolCall::GitStatus {} => output_result("git_status", git::status(workdir).await),
+        ToolCall::GitDiff { path, staged } => output_result("git_diff", git::diff(workdir, path.as_deref(), staged).await),
+        ToolCall::GitLog { path, limit } => output_result("git_log", git::log(workdir, path.as_deref(), limit).await),
+        ToolCall::GitBlame { path, start, end } => output_result("git_blame", git::blame(workdir, &path, start, end).await),
+        ToolCall::GitCommit { message, files } => output_result("git_commit", git::commit(workdir, &message, &files).await),
+        ToolCall::ListSymbols { path } => output_result("list_symbols", symbols::list(workdir, &path)),
+        ToolCall::FindDefinition { name } => output_result("find_definition", symbols::definition(workdir, &name)),
+        ToolCall::FindReferences { name } => output_result("find_references", symbols::references(workdir, &name)),
+        ToolCall::AnalyzeFile { path } => output_result("analyze_file", analyze_file(workdir, &path)),
+        ToolCall::DocsLookup { query, limit } => {
+            output_result("docs_lookup", docs::lookup(workdir, &config.docs_dirs.value, &query, limit))
+        }
+        ToolCall::FetchUrl { url, refresh } => output_result("fetch_url", docs::fetch_url(&url, refresh).await),
+        ToolCall::ViewImage { path } => match Image::load(&workdir.join(&path)) {
+            Ok(image) => {
+                let output = format!("Attached {} ({} KB); it comes with the next message.", path, image.bytes.div_ceil(1024));
+                images.push(image);
+                ToolResult {
+                    tool_name: "view_image".to_string(),
+                    output,
+                    success: true,
+                }
+            }
+            Err(e) => ToolResult {
+                tool_name: "view_image".to_string(),
+                output: e,
+                success: false,
+            },
+        },
+        ToolCall::UpdatePlan { steps } => ToolResult {
+            tool_name: "update_plan".to_string(),
+            output: plan.update(steps),
+            success: true,
+        },
+        ToolCall::CompleteStep { step, note } => match plan.complete_step(step, note) {
+            Ok(output) => ToolResult {
+                tool_name: "complete_step".to_string(),
+                output,
+                success: true,
+            },
+            Err(e) => ToolResult {
+                tool_name: "complete_step".to_string(),
+                output: e,
+                success: false,
+            },
+        },
+        // The agent verifies before accepting; on its own the call just reports back
+        ToolCall::Finish { summary } => ToolResult {
+            tool_name: "finish".to_string(),
+            output: format!("Finished: {}", summary),
+            success: true,
+        },
+    }
+}
+
+pub async fn run_command(tool_name: &str, command: &str, workdir: &Path) -> ToolResult {
+    let mut shell = if cfg!(target_os = "windows") {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    };
+    shell.current_dir(workdir);
+
+    match process::output(shell).await {
+        Ok(out) => {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            let combined = format!("{}{}", stdout, stderr);
+            let output = match (combined.is_empty(), out.status.success()) {
+                (false, _) => combined,
+                (true, true) => "Success (no output)".to_string(),
+                (true, false) => format!("Failed with {} (no output)", out.status),
+            };
+            ToolResult {
+                tool_name: tool_name.to_string(),
+                output,
+                success: out.status.success(),
+            }
+        }
+        Err(e) => ToolResult {
+            tool_name: tool_name.to_string(),
+            output: format!("Failed to execute command: {}", e),
+            success: false,
+        },
+    }
+}
+
+/// Writes through a temporary file next to `path` and renames it into place,
+/// so a cancelled or failed write never leaves a half-written file.
+async fn write_file(path: &Path, content: &str) -> io::Result<()> {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    let temp = TempFile(path.with_file_name(format!(".{}.rumi-tmp", name)));
+    tokio::fs::write(&temp.0, content).await?;
+    if let Ok(existing) = tokio::fs::metadata(path).await {
+        tokio::fs::set_permissions(&temp.0, existing.permissions()).await?;
+    }
+    tokio::fs::rename(&temp.0, path).await
+}
+
+/// Removed on drop unless it was renamed away.
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Fails when `path` is absolute or has enough `..` to point outside
+/// `workdir`. Checked lexically, so a symlink inside the worktree is still
+/// followed.
+fn inside(workdir: &Path, path: &str) -> Result<(), String> {
+    let root = normalize(&std::path::absolute(workdir).map_err(|e| e.to_string())?);
+    if normalize(&root.join(path)).starts_with(&root) {
+        Ok(())
+    } else {
+        Err(format!("{} is outside the working directory; paths are relative to it.", path))
+    }
+}
+
+/// Resolves `.` and `..` without touching the file system. A `..` that
+/// goes above a relative path's start is kept.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(out.components().next_back(), Some(Component::Normal(_))) => {
+                out.pop();
+            }
+            // `/..` is `/`
+            Component::ParentDir if out.has_root() => {}
+            component => out.push(component),
+        }
+    }
+    out
+}
+
+fn output_result(tool_name: &str, (output, success): (String, bool)) -> ToolResult {
+    ToolResult {
+        tool_name: tool_name.to_string(),
+        output,
+        success,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A distinct number per variant; a new variant does not compile until it
+    /// is listed here, and then fails the test until it has an example.
+    fn variant(call: &ToolCall) -> usize {
+        match call {
+            ToolCall::ReadFile { .. } => 0,
+            ToolCall::WriteFile { .. } => 1,
+            ToolCall::RunShell { .. } => 2,
+            ToolCall::Check { .. } => 3,
+            ToolCall::Remember { .. } => 4,
+            ToolCall::GitStatus {} => 5,
+            ToolCall::GitDiff { .. } => 6,
+            ToolCall::GitLog { .. } => 7,
+            ToolCall::GitBlame { .. } => 8,
+            ToolCall::GitCommit { .. } => 9,
+            ToolCall::ListSymbols { .. } => 10,
+            ToolCall::FindDefinition { .. } => 11,
+            ToolCall::FindReferences { .. } => 12,
+            ToolCall::AnalyzeFile { .. } => 13,
+            ToolCall::ViewImage { .. } => 14,
+            ToolCall::DocsLookup { .. } => 15,
+            ToolCall::FetchUrl { .. } => 16,
+            ToolCall::UpdatePlan { .. } => 17,
+            ToolCall::CompleteStep { .. } => 18,
+            ToolCall::Finish { .. } => 19,
+            // Built from the config, so they have no examples
+            ToolCall::Custom { .. } | ToolCall::Mcp { .. } => usize::MAX,
+        }
+    }
+
+    #[test]
+    fn test_every_builtin_tool_has_an_example() {
+        let mut variants: Vec<usize> = ToolCall::examples().iter().map(variant).collect();
+        variants.sort();
+        assert_eq!(variants, (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(Path::new("./src/../lib/./a.rs")), Path::new("lib/a.rs"));
+        assert_eq!(normalize(Path::new("../a/../../b")), Path::new("../../b"));
+        assert_eq!(normalize(Path::new("/../etc")), Path::new("/etc"));
+    }
+
+    #[tokio::test]
+    async fn test_paths_stay_in_the_workdir() {
+        let root = std::env::temp_dir().join(format!("rumi-tools-{}", std::process::id()));
+        let workdir = root.join("work");
+        fs::create_dir_all(workdir.join("src")).unwrap();
+        fs::write(root.join("secret.txt"), "outside").unwrap();
+        fs::write(workdir.join("a.txt"), "inside").unwrap();
+        let (config, mut plan, mut images) = (Config::default(), Plan::default(), Vec::new());
+        let mut run = async |call| execute_tool(call, &mut plan, &mut images, &workdir, &config).await;
+
+        let read = |path: &str| ToolCall::ReadFile { path: path.to_string() };
+        assert_eq!(run(read("src/../a.txt")).await.output, "inside");
+        let outside = run(read("../secret.txt")).await;
+        assert!(!outside.success);
+        assert_eq!(outside.output, "../secret.txt is outside the working directory; paths are relative to it.");
+        let absolute = root.join("secret.txt").display().to_string();
+        assert!(!run(read(&absolute)).await.success);
+        assert!(run(read(&workdir.join("a.txt").display().to_string())).await.success);
+
+        let write = ToolCall::WriteFile { path: "src/../../b.txt".to_string(), content: "x".to_string() };
+        assert!(!run(write).await.success);
+        assert!(!root.join("b.txt").exists());
+        assert!(!run(ToolCall::ViewImage { path: "../secret.png".to_string() }).await.success);
+        assert!(!run(ToolCall::Check { path: Some("..".to_string()) }).await.success);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_examples_parse_back() {
+        for example in ToolCall::examples() {
+            let json = serde_json::to_value(&example).unwrap();
+            assert_eq!(json["tool"], example.name());
+            let parsed: ToolCall = serde_json::from_value(json.clone()).unwrap_or_else(|e| panic!("{}: {}", example.name(), e));
+            assert_eq!(serde_json::to_value(&parsed).unwrap(), json);
+        }
     }
 }
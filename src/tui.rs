@@ -0,0 +1,469 @@
+use crate::config::Config;
+use crate::events::AgentEvent;
+use crate::interrupt::Interrupt;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::io;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::time::Duration;
+
+/// What the status bar shows; updated from `Thinking` and `Usage` events.
+pub struct Status {
+    model: String,
+    temperature: f32,
+    attempt: u32,
+    max_loops: u32,
+    context_tokens: u32,
+    budget: u32,
+    session_tokens: u64,
+}
+
+impl Status {
+    pub fn new(config: &Config) -> Self {
+        Status {
+            model: config.model.value.clone(),
+            temperature: config.base_temperature.value,
+            attempt: 0,
+            max_loops: config.max_loops.value,
+            context_tokens: 0,
+            // Replaced by the picked profile's context with the first `Thinking`
+            budget: config.context_tokens.value,
+            session_tokens: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Pane {
+    Conversation,
+    Tools,
+    Diff,
+}
+
+struct ToolEntry {
+    name: String,
+    output: String,
+    success: bool,
+    expanded: bool,
+}
+
+struct App {
+    status: Status,
+    conversation: Vec<(String, Style)>,
+    /// Lines scrolled up from the bottom of the conversation; 0 follows new output.
+    conversation_scroll: usize,
+    tools: Vec<ToolEntry>,
+    selected_tool: usize,
+    diffs: Vec<(String, String)>,
+    selected_diff: usize,
+    diff_scroll: usize,
+    focus: Pane,
+    /// Summary of the tool call waiting for y/n, if any.
+    pending_approval: Option<String>,
+    /// What the user is typing after an interrupt, while the agent waits for it.
+    follow_up: Option<String>,
+    /// The agent dropped its end of the channel.
+    done: bool,
+}
+
+/// Full-screen view of one agent run; approval answers go back on `approvals`
+/// and what to do after an interrupt on `instructions`. Returns when the user
+/// quits; the caller stops the agent if it is still running.
+pub fn run(
+    events: Receiver<AgentEvent>,
+    approvals: Sender<bool>,
+    instructions: Sender<String>,
+    interrupt: Interrupt,
+    status: Status,
+) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, events, approvals, instructions, interrupt, status);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(
+    terminal: &mut DefaultTerminal,
+    events: Receiver<AgentEvent>,
+    approvals: Sender<bool>,
+    instructions: Sender<String>,
+    interrupt: Interrupt,
+    status: Status,
+) -> io::Result<()> {
+    let mut app = App::new(status);
+
+    loop {
+        while !app.done {
+            match events.try_recv() {
+                Ok(event) => app.apply(event),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => app.done = true,
+            }
+        }
+        terminal.draw(|frame| app.draw(frame))?;
+
+        if !event::poll(Duration::from_millis(100))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        // Raw mode swallows SIGINT, so Ctrl-C has to be handled as a key: the
+        // first press interrupts the agent, a second one quits
+        let ctrl_c = key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c');
+        if ctrl_c && !app.done && !interrupt.press() {
+            app.say("Interrupting...".to_string(), Style::default().fg(Color::Yellow));
+            continue;
+        }
+        if ctrl_c {
+            return Ok(());
+        }
+        if let Some(text) = &mut app.follow_up {
+            match key.code {
+                KeyCode::Char(c) => text.push(c),
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                // Sending nothing stops the run
+                KeyCode::Enter | KeyCode::Esc => {
+                    if key.code == KeyCode::Esc {
+                        text.clear();
+                    }
+                    let text = app.follow_up.take().unwrap_or_default();
+                    let shown = if text.is_empty() { "(stop)".to_string() } else { text.clone() };
+                    app.say(format!("> {}", shown), Style::default().fg(Color::Yellow));
+                    let _ = instructions.send(text);
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+            return Ok(());
+        }
+        if app.pending_approval.is_some() && matches!(key.code, KeyCode::Char('y' | 'n')) {
+            let approved = key.code == KeyCode::Char('y');
+            let summary = app.pending_approval.take().unwrap_or_default();
+            let verdict = if approved { "Approved" } else { "Denied" };
+            app.say(format!("{}: {}", verdict, summary), Style::default().fg(Color::Yellow));
+            let _ = approvals.send(approved);
+            continue;
+        }
+        app.handle_key(key.code);
+    }
+}
+
+impl App {
+    fn new(status: Status) -> Self {
+        App {
+            status,
+            conversation: Vec::new(),
+            conversation_scroll: 0,
+            tools: Vec::new(),
+            selected_tool: 0,
+            diffs: Vec::new(),
+            selected_diff: 0,
+            diff_scroll: 0,
+            focus: Pane::Conversation,
+            pending_approval: None,
+            follow_up: None,
+            done: false,
+        }
+    }
+
+    fn apply(&mut self, event: AgentEvent) {
+        let dim = Style::default().fg(Color::DarkGray);
+        match event {
+            AgentEvent::Thinking { temperature, attempt, model, context_tokens } => {
+                self.status.model = model.clone();
+                self.status.temperature = temperature;
+                self.status.attempt = attempt;
+                self.status.budget = context_tokens;
+                self.say(format!("Thinking with {} at temp {:.2}, attempt {}", model, temperature, attempt), dim);
+            }
+            AgentEvent::Assistant { text } => {
+                self.say(String::new(), Style::default());
+                self.say("Rumi".to_string(), Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD));
+                for line in text.lines() {
+                    self.say(line.to_string(), Style::default());
+                }
+            }
+            AgentEvent::ToolCall { .. } | AgentEvent::Done { .. } => {}
+            AgentEvent::ApprovalRequest { tool, summary } => {
+                // Jump to the diff so the write can be reviewed before answering
+                if tool == "write_file" {
+                    self.focus = Pane::Diff;
+                }
+                self.pending_approval = Some(summary);
+            }
+            AgentEvent::FollowUpRequest => {
+                self.focus = Pane::Conversation;
+                self.follow_up = Some(String::new());
+            }
+            AgentEvent::PendingWrite { path, diff } => {
+                self.diffs.push((path, diff));
+                self.selected_diff = self.diffs.len() - 1;
+                self.diff_scroll = 0;
+            }
+            AgentEvent::ToolResult { name, output, success } => {
+                let marker = if success { "ok" } else { "failed" };
+                self.say(format!("-> {} ({})", name, marker), dim);
+                self.tools.push(ToolEntry { name, output, success, expanded: false });
+                self.selected_tool = self.tools.len() - 1;
+            }
+            AgentEvent::Usage { context_tokens, session_tokens, .. } => {
+                self.status.context_tokens = context_tokens;
+                self.status.session_tokens = session_tokens;
+            }
+            AgentEvent::Info { text } => self.say(text.trim().to_string(), Style::default().fg(Color::Yellow)),
+            AgentEvent::Error { text } => self.say(format!("Error: {}", text), Style::default().fg(Color::Red)),
+        }
+    }
+
+    fn say(&mut self, text: String, style: Style) {
+        self.conversation.push((text, style));
+    }
+
+    fn handle_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Tab => {
+                self.focus = match self.focus {
+                    Pane::Conversation => Pane::Tools,
+                    Pane::Tools => Pane::Diff,
+                    Pane::Diff => Pane::Conversation,
+                }
+            }
+            KeyCode::Up => self.scroll(-1),
+            KeyCode::Down => self.scroll(1),
+            KeyCode::PageUp => self.scroll(-10),
+            KeyCode::PageDown => self.scroll(10),
+            KeyCode::Enter | KeyCode::Char(' ') if self.focus == Pane::Tools => {
+                if let Some(tool) = self.tools.get_mut(self.selected_tool) {
+                    tool.expanded = !tool.expanded;
+                }
+            }
+            KeyCode::Left if self.focus == Pane::Diff => {
+                self.selected_diff = self.selected_diff.saturating_sub(1);
+                self.diff_scroll = 0;
+            }
+            KeyCode::Right if self.focus == Pane::Diff => {
+                self.selected_diff = (self.selected_diff + 1).min(self.diffs.len().saturating_sub(1));
+                self.diff_scroll = 0;
+            }
+            _ => {}
+        }
+    }
+
+    /// Positive `delta` moves down / towards newer output.
+    fn scroll(&mut self, delta: isize) {
+        let step = |value: usize, delta: isize| value.saturating_add_signed(delta);
+        match self.focus {
+            Pane::Conversation => self.conversation_scroll = step(self.conversation_scroll, -delta),
+            Pane::Tools => {
+                self.selected_tool = step(self.selected_tool, delta).min(self.tools.len().saturating_sub(1));
+            }
+            Pane::Diff => self.diff_scroll = step(self.diff_scroll, delta),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] = Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] = Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)]).areas(main);
+        let [tools, diff] = Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(right);
+
+        self.draw_conversation(frame, left);
+        self.draw_tools(frame, tools);
+        self.draw_diff(frame, diff);
+        self.draw_status(frame, status);
+    }
+
+    fn block(&self, title: String, pane: Pane) -> Block<'static> {
+        let style = if self.focus == pane {
+            Style::default().fg(Color::Cyan)
+        } else {
+            Style::default()
+        };
+        Block::default().borders(Borders::ALL).border_style(style).title(title)
+    }
+
+    fn draw_conversation(&mut self, frame: &mut Frame, area: Rect) {
+        let width = area.width.saturating_sub(2) as usize;
+        let height = area.height.saturating_sub(2) as usize;
+        let lines: Vec<Line> = self
+            .conversation
+            .iter()
+            .flat_map(|(text, style)| wrap(text, width).into_iter().map(|l| Line::styled(l, *style)))
+            .collect();
+
+        // Keep the offset from the bottom in range, so scrolling back down is immediate
+        let max_scroll = lines.len().saturating_sub(height);
+        self.conversation_scroll = self.conversation_scroll.min(max_scroll);
+        let top = max_scroll - self.conversation_scroll;
+        let title = if self.conversation_scroll > 0 {
+            format!(" Conversation (+{} below) ", self.conversation_scroll)
+        } else {
+            " Conversation ".to_string()
+        };
+        let paragraph = Paragraph::new(lines).block(self.block(title, Pane::Conversation)).scroll((top as u16, 0));
+        frame.render_widget(paragraph, area);
+    }
+
+    fn draw_tools(&self, frame: &mut Frame, area: Rect) {
+        let width = area.width.saturating_sub(2) as usize;
+        let height = area.height.saturating_sub(2) as usize;
+        let mut lines = Vec::new();
+        let mut selected_line = 0;
+        for (i, tool) in self.tools.iter().enumerate() {
+            if i == self.selected_tool {
+                selected_line = lines.len();
+            }
+            let marker = if tool.expanded { "v" } else { ">" };
+            let color = if tool.success { Color::Green } else { Color::Red };
+            let summary = tool.output.lines().next().unwrap_or("");
+            let mut style = Style::default().fg(color);
+            if i == self.selected_tool && self.focus == Pane::Tools {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            let header = format!("{} {} {}", marker, tool.name, summary);
+            lines.push(Line::styled(header.chars().take(width).collect::<String>(), style));
+            if tool.expanded {
+                for line in tool.output.lines() {
+                    for part in wrap(line, width.saturating_sub(2)) {
+                        lines.push(Line::raw(format!("  {}", part)));
+                    }
+                }
+            }
+        }
+
+        // Keep the selected entry on screen
+        let top = selected_line.saturating_sub(height.saturating_sub(1));
+        let title = format!(" Tools ({}) ", self.tools.len());
+        let paragraph = Paragraph::new(lines).block(self.block(title, Pane::Tools)).scroll((top as u16, 0));
+        frame.render_widget(paragraph, area);
+    }
+
+    fn draw_diff(&self, frame: &mut Frame, area: Rect) {
+        let Some((path, diff)) = self.diffs.get(self.selected_diff) else {
+            let empty = Paragraph::new("No pending writes.").block(self.block(" Diff ".to_string(), Pane::Diff));
+            frame.render_widget(empty, area);
+            return;
+        };
+        let lines: Vec<Line> = diff
+            .lines()
+            .map(|line| {
+                let color = match line.chars().next() {
+                    Some('+') if !line.starts_with("+++") => Color::Green,
+                    Some('-') if !line.starts_with("---") => Color::Red,
+                    Some('@') => Color::Cyan,
+                    _ => Color::Reset,
+                };
+                Line::styled(line.to_string(), Style::default().fg(color))
+            })
+            .collect();
+        let title = format!(" Diff {}/{}: {} ", self.selected_diff + 1, self.diffs.len(), path);
+        let paragraph = Paragraph::new(lines).block(self.block(title, Pane::Diff)).scroll((self.diff_scroll as u16, 0));
+        frame.render_widget(paragraph, area);
+    }
+
+    fn draw_status(&self, frame: &mut Frame, area: Rect) {
+        let status = &self.status;
+        let state = if self.done { "done, q to exit" } else { "running" };
+        let text = format!(
+            " {} | temp {:.2} | loop {}/{} | ctx {}/{} tokens | session {} tokens | {} | Tab focus, arrows scroll, Enter expand, q quit",
+            status.model,
+            status.temperature,
+            status.attempt,
+            status.max_loops + 1,
+            status.context_tokens,
+            status.budget,
+            status.session_tokens,
+            state,
+        );
+        if let Some(text) = &self.follow_up {
+            let text = format!(" What next? {}_  (/compact summarizes the history, Enter on nothing stops, Ctrl-C quits) ", text);
+            let style = Style::default().bg(Color::Yellow).fg(Color::Black).add_modifier(Modifier::BOLD);
+            frame.render_widget(Paragraph::new(text).style(style), area);
+            return;
+        }
+        if let Some(summary) = &self.pending_approval {
+            let text = format!(" Allow {}? y/n ", summary);
+            let style = Style::default().bg(Color::Yellow).fg(Color::Black).add_modifier(Modifier::BOLD);
+            frame.render_widget(Paragraph::new(text).style(style), area);
+            return;
+        }
+        let style = if status.context_tokens > status.budget * 9 / 10 {
+            Style::default().bg(Color::Red).fg(Color::White)
+        } else {
+            Style::default().bg(Color::Blue).fg(Color::White)
+        };
+        frame.render_widget(Paragraph::new(Line::from(Span::styled(text, style))).style(style), area);
+    }
+}
+
+/// Hard-wraps a line to `width` characters so scroll offsets match what is drawn.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    if width == 0 || text.is_empty() {
+        return vec![text.to_string()];
+    }
+    let chars: Vec<char> = text.chars().collect();
+    chars.chunks(width).map(|chunk| chunk.iter().collect()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app() -> App {
+        App::new(Status::new(&Config::default()))
+    }
+
+    #[test]
+    fn test_thinking_updates_the_status() {
+        let mut app = app();
+        app.apply(AgentEvent::Thinking { temperature: 0.5, attempt: 2, model: "small".to_string(), context_tokens: 4096 });
+        assert_eq!((app.status.model.as_str(), app.status.temperature, app.status.attempt), ("small", 0.5, 2));
+        assert_eq!(app.status.budget, 4096);
+        assert_eq!(app.conversation.last().unwrap().0, "Thinking with small at temp 0.50, attempt 2");
+
+        app.apply(AgentEvent::Usage { status_line: String::new(), context_tokens: 3000, session_tokens: 9000 });
+        assert_eq!((app.status.context_tokens, app.status.session_tokens), (3000, 9000));
+    }
+
+    #[test]
+    fn test_writes_and_tool_results_fill_their_panes() {
+        let mut app = app();
+        app.apply(AgentEvent::PendingWrite { path: "a.rs".to_string(), diff: "+a".to_string() });
+        app.apply(AgentEvent::PendingWrite { path: "b.rs".to_string(), diff: "+b".to_string() });
+        assert_eq!((app.diffs.len(), app.selected_diff), (2, 1));
+
+        app.apply(AgentEvent::ApprovalRequest { tool: "write_file".to_string(), summary: "write_file b.rs".to_string() });
+        assert!(app.focus == Pane::Diff);
+        assert_eq!(app.pending_approval.as_deref(), Some("write_file b.rs"));
+
+        app.apply(AgentEvent::ToolResult { name: "check".to_string(), output: "error".to_string(), success: false });
+        assert_eq!((app.tools.len(), app.selected_tool, app.tools[0].success), (1, 0, false));
+        assert_eq!(app.conversation.last().unwrap().0, "-> check (failed)");
+    }
+
+    #[test]
+    fn test_follow_up_request_opens_the_input() {
+        let mut app = app();
+        app.focus = Pane::Tools;
+        app.apply(AgentEvent::FollowUpRequest);
+        assert!(app.focus == Pane::Conversation);
+        assert_eq!(app.follow_up.as_deref(), Some(""));
+
+        app.apply(AgentEvent::Assistant { text: "one\ntwo".to_string() });
+        let lines: Vec<&str> = app.conversation.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(lines, ["", "Rumi", "one", "two"]);
+        app.apply(AgentEvent::Error { text: "boom".to_string() });
+        assert_eq!(app.conversation.last().unwrap().0, "Error: boom");
+    }
+}
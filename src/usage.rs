@@ -0,0 +1,180 @@
+use crate::models::DEFAULT_PROFILE;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+const USAGE_LOG: &str = ".rumi/usage.jsonl";
+
+/// Token and timing numbers for one model turn and the tool it triggered.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TurnUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    /// True when the server did not report usage and the counts are estimated.
+    #[serde(default)]
+    pub estimated: bool,
+    pub ttft_ms: u64,
+    pub latency_ms: u64,
+    #[serde(default)]
+    pub tool: Option<String>,
+    #[serde(default)]
+    pub tool_ms: u64,
+    /// The model that answered, when profiles route turns to different ones.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// The profile that answered; turns recorded before profiles had one are `default`'s.
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// What the turn cost at its profile's prices.
+    #[serde(default)]
+    pub cost: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct UsageTotals {
+    pub turns: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub latency_ms: u64,
+    pub tool_ms: u64,
+    pub avg_ttft_ms: u64,
+    pub cost: f64,
+}
+
+impl UsageTotals {
+    /// Adds another session's totals, e.g. to sum up parallel sub-agents.
+    pub fn merge(&mut self, other: &UsageTotals) {
+        let turns = self.turns + other.turns;
+        if turns > 0 {
+            self.avg_ttft_ms = (self.avg_ttft_ms * self.turns as u64 + other.avg_ttft_ms * other.turns as u64) / turns as u64;
+        }
+        self.turns = turns;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.latency_ms += other.latency_ms;
+        self.tool_ms += other.tool_ms;
+        self.cost += other.cost;
+    }
+}
+
+/// Per-turn usage of a session.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct UsageLog {
+    pub turns: Vec<TurnUsage>,
+}
+
+impl UsageLog {
+    pub fn push(&mut self, turn: TurnUsage) {
+        self.turns.push(turn);
+    }
+
+    pub fn totals(&self) -> UsageTotals {
+        let mut totals = UsageTotals {
+            turns: self.turns.len(),
+            ..UsageTotals::default()
+        };
+        for turn in &self.turns {
+            totals.prompt_tokens += turn.prompt_tokens as u64;
+            totals.completion_tokens += turn.completion_tokens as u64;
+            totals.latency_ms += turn.latency_ms;
+            totals.tool_ms += turn.tool_ms;
+            totals.avg_ttft_ms += turn.ttft_ms;
+            totals.cost += turn.cost;
+        }
+        if totals.turns > 0 {
+            totals.avg_ttft_ms /= totals.turns as u64;
+        }
+        totals
+    }
+
+    /// The turns `profile` answered.
+    pub fn for_profile(&self, profile: &str) -> UsageLog {
+        let turns = self.turns.iter().filter(|turn| turn.profile.as_deref().unwrap_or(DEFAULT_PROFILE) == profile).cloned().collect();
+        UsageLog { turns }
+    }
+
+    /// One-line running total printed after every turn.
+    pub fn status_line(&self) -> String {
+        let last = self.turns.last().cloned().unwrap_or_default();
+        let totals = self.totals();
+        format!(
+            "[turn {} | {} in / {} out{} | ttft {:.2}s | gen {:.2}s | tool {:.2}s | session {} tokens{}]",
+            totals.turns,
+            last.prompt_tokens,
+            last.completion_tokens,
+            if last.estimated { " (est.)" } else { "" },
+            last.ttft_ms as f64 / 1000.0,
+            last.latency_ms as f64 / 1000.0,
+            last.tool_ms as f64 / 1000.0,
+            totals.prompt_tokens + totals.completion_tokens,
+            cost_suffix(totals.cost),
+        )
+    }
+
+    /// Summary printed when the session ends.
+    pub fn summary(&self) -> String {
+        let totals = self.totals();
+        format!(
+            "--- Session Usage ---\nTurns: {}\nTokens: {} prompt + {} completion = {}\nModel time: {:.1}s (avg time-to-first-token {:.2}s)\nTool time: {:.1}s{}",
+            totals.turns,
+            totals.prompt_tokens,
+            totals.completion_tokens,
+            totals.prompt_tokens + totals.completion_tokens,
+            totals.latency_ms as f64 / 1000.0,
+            totals.avg_ttft_ms as f64 / 1000.0,
+            totals.tool_ms as f64 / 1000.0,
+            if totals.cost > 0.0 { format!("\nCost: {:.4}", totals.cost) } else { String::new() },
+        )
+    }
+}
+
+fn cost_suffix(cost: f64) -> String {
+    if cost > 0.0 { format!(" | cost {:.4}", cost) } else { String::new() }
+}
+
+/// One line of `.rumi/usage.jsonl`, for comparing models and prompt changes across sessions.
+#[derive(Serialize)]
+pub struct UsageRecord<'a> {
+    pub session: &'a str,
+    pub task: &'a str,
+    pub totals: UsageTotals,
+    /// The share of each profile that answered turns.
+    pub profiles: Vec<ProfileUsage>,
+    pub turns: &'a [TurnUsage],
+}
+
+#[derive(Serialize)]
+pub struct ProfileUsage {
+    pub profile: String,
+    pub model: String,
+    pub cost_per_1k_prompt: f64,
+    pub cost_per_1k_completion: f64,
+    pub totals: UsageTotals,
+}
+
+pub fn export(record: &UsageRecord) -> std::io::Result<()> {
+    fs::create_dir_all(".rumi")?;
+    let mut file = OpenOptions::new().create(true).append(true).open(USAGE_LOG)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(profile: Option<&str>, prompt_tokens: u32, cost: f64) -> TurnUsage {
+        TurnUsage { prompt_tokens, profile: profile.map(str::to_string), cost, ..TurnUsage::default() }
+    }
+
+    #[test]
+    fn test_usage_per_profile() {
+        let turns = vec![turn(Some("default"), 100, 0.0), turn(Some("cloud"), 200, 0.5), turn(None, 50, 0.0), turn(Some("cloud"), 300, 0.25)];
+        let log = UsageLog { turns };
+        let cloud = log.for_profile("cloud").totals();
+        assert_eq!((cloud.turns, cloud.prompt_tokens, cloud.cost), (2, 500, 0.75));
+        // Turns from before profiles were recorded count as the default profile's
+        let default = log.for_profile(DEFAULT_PROFILE).totals();
+        assert_eq!((default.turns, default.prompt_tokens, default.cost), (2, 150, 0.0));
+        assert_eq!(log.totals().cost, 0.75);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::diagnostics::tail;
+use crate::tools::run_command;
+use std::path::Path;
+
+/// Longest check output or diff quoted to the reviewer.
+const MAX_QUOTED_LINES: usize = 80;
+
+/// Runs the `[verify] checks` commands in `workdir`. Returns whether all of
+/// them passed, and a report with the tail of each failing command's output.
+pub async fn run_checks(checks: &[String], workdir: &Path) -> (bool, String) {
+    let mut passed = true;
+    let mut report = String::new();
+    for check in checks {
+        let result = run_command("verify", check, workdir).await;
+        if result.success {
+            report.push_str(&format!("PASS {}\n", check));
+        } else {
+            passed = false;
+            report.push_str(&format!("FAIL {}\n{}\n", check, tail(&result.output, MAX_QUOTED_LINES)));
+        }
+    }
+    (passed, report)
+}
+
+/// What the reviewer sees: the task, the agent's summary, the checks and the diff.
+pub fn review_request(task: &str, summary: &str, checks: &str, diff: &str) -> String {
+    let checks = if checks.is_empty() { "(none configured)" } else { checks };
+    format!(
+        "ORIGINAL REQUEST:\n{}\n\nAGENT'S SUMMARY:\n{}\n\nCHECKS:\n{}\n\nUNCOMMITTED CHANGES:\n{}",
+        task,
+        summary,
+        checks.trim_end(),
+        head(diff)
+    )
+}
+
+/// `Ok` for a PASS verdict, otherwise the reviewer's reasons. The verdict is
+/// the first word of the first line, read through markdown emphasis, so
+/// `**PASS**` passes and `PASSED? No` does not.
+pub fn parse_verdict(answer: &str) -> Result<(), String> {
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Err("The reviewer gave no answer.".to_string());
+    }
+    let first = answer.lines().next().unwrap_or_default();
+    let verdict: String = first
+        .trim_start_matches(|c: char| c.is_whitespace() || matches!(c, '*' | '_' | '#' | '`' | '>'))
+        .chars()
+        .take_while(char::is_ascii_alphabetic)
+        .collect();
+    if verdict.eq_ignore_ascii_case("PASS") {
+        return Ok(());
+    }
+    let reasons = answer.split_once('\n').map_or(answer, |(_, rest)| rest).trim();
+    Err(if reasons.is_empty() { answer.to_string() } else { reasons.to_string() })
+}
+
+/// The first lines, where `git_diff` puts its `--stat` summary.
+fn head(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut out = lines[..lines.len().min(MAX_QUOTED_LINES)].join("\n");
+    if lines.len() > MAX_QUOTED_LINES {
+        out.push_str(&format!("\n... ({} lines cut)", lines.len() - MAX_QUOTED_LINES));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pass() {
+        for answer in ["PASS", "  pass\n", "**PASS**", "## PASS: all checks green", "`PASS`.\nLooks good."] {
+            assert_eq!(parse_verdict(answer), Ok(()), "{:?}", answer);
+        }
+    }
+
+    #[test]
+    fn test_fail_with_reasons() {
+        assert_eq!(parse_verdict("FAIL\n- no test for the parser\n- README not updated\n"), Err("- no test for the parser\n- README not updated".to_string()));
+        assert_eq!(parse_verdict("**FAIL**\nThe flag is ignored."), Err("The flag is ignored.".to_string()));
+        // Not a PASS, whatever follows
+        assert_eq!(parse_verdict("PASSED? No, the build fails."), Err("PASSED? No, the build fails.".to_string()));
+        assert_eq!(parse_verdict("PASSABLE\nbut untested"), Err("but untested".to_string()));
+    }
+
+    #[test]
+    fn test_empty_answer_fails() {
+        assert_eq!(parse_verdict(" \n "), Err("The reviewer gave no answer.".to_string()));
+    }
+
+    #[test]
+    fn test_head() {
+        let short = "a\nb";
+        assert_eq!(head(short), short);
+        let long: Vec<String> = (0..MAX_QUOTED_LINES + 5).map(|i| i.to_string()).collect();
+        let out = head(&long.join("\n"));
+        assert_eq!(out.lines().count(), MAX_QUOTED_LINES + 1);
+        assert!(out.ends_with(&format!("{}\n... (5 lines cut)", MAX_QUOTED_LINES - 1)));
+    }
+}